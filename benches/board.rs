@@ -0,0 +1,89 @@
+//! Compares the bitboard-backed `Board` with the previous `[Field; 64]`
+//! representation on the move generation hot path.
+//!
+//! Run with `cargo bench --bench board`.
+
+use std::{hint::black_box, time::Instant};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use socha_client_2023::game::{Board, Field, Move, Team, Vec2, Doubled, BOARD_FIELDS, BOARD_SIZE, MAX_FISH, PENGUINS_PER_TEAM};
+
+const POSITIONS: usize = 1_000;
+const ROUNDS: usize = 200;
+
+/// The previous board representation, which walks each
+/// direction one field at a time.
+struct ArrayBoard {
+    fields: [Field; BOARD_FIELDS],
+}
+
+impl ArrayBoard {
+    fn from_board(board: &Board) -> Self {
+        let mut fields = [Field::EMPTY; BOARD_FIELDS];
+        for (c, f) in board.fields() {
+            fields[Board::index_for(c)] = f;
+        }
+        Self { fields }
+    }
+
+    fn get(&self, coords: Vec2<Doubled>) -> Option<Field> {
+        if Board::in_bounds(coords) {
+            Some(self.fields[Board::index_for(coords)])
+        } else {
+            None
+        }
+    }
+
+    fn possible_moves_from(&self, coords: Vec2<Doubled>) -> impl Iterator<Item=Move> + '_ {
+        Vec2::<Doubled>::DIRECTIONS
+            .into_iter()
+            .flat_map(move |v| (1..BOARD_SIZE as i32)
+                .map(move |n| Move::sliding(coords, n * v))
+                .take_while(|c| self.get(c.to()).unwrap_or_default().fish() > 0))
+    }
+}
+
+fn random_board(rng: &mut StdRng) -> Board {
+    let mut board = Board::EMPTY;
+    for i in 0..BOARD_FIELDS {
+        board.set(Board::coords_for(i), Field::with_fish(rng.gen_range(0..=MAX_FISH)));
+    }
+    for team in [Team::One, Team::Two] {
+        for _ in 0..PENGUINS_PER_TEAM {
+            board.set(Board::coords_for(rng.gen_range(0..BOARD_FIELDS)), Field::with_penguin(team));
+        }
+    }
+    board
+}
+
+fn bench(name: &str, calls: usize, mut f: impl FnMut() -> usize) {
+    let start = Instant::now();
+    let mut moves = 0;
+    for _ in 0..ROUNDS {
+        moves += black_box(f());
+    }
+    let elapsed = start.elapsed();
+    println!("{:<12} {:>8.1} ns/call ({} moves)", name, elapsed.as_nanos() as f64 / (ROUNDS * calls) as f64, moves);
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(2023);
+    let boards: Vec<Board> = (0..POSITIONS).map(|_| random_board(&mut rng)).collect();
+    let array_boards: Vec<ArrayBoard> = boards.iter().map(ArrayBoard::from_board).collect();
+    let penguins: Vec<Vec<Vec2<Doubled>>> = boards.iter().map(|b| b.penguins().map(|(c, _)| c).collect()).collect();
+    let calls = penguins.iter().map(|p| p.len()).sum();
+
+    // Both representations have to agree before their speed is worth comparing
+    for ((board, array_board), penguins) in boards.iter().zip(&array_boards).zip(&penguins) {
+        for &p in penguins {
+            assert_eq!(board.possible_moves_from(p).collect::<Vec<_>>(), array_board.possible_moves_from(p).collect::<Vec<_>>());
+        }
+    }
+
+    bench("array", calls, || array_boards.iter().zip(&penguins)
+        .map(|(b, ps)| ps.iter().map(|&p| b.possible_moves_from(black_box(p)).count()).sum::<usize>())
+        .sum());
+    bench("bitboard", calls, || boards.iter().zip(&penguins)
+        .map(|(b, ps)| ps.iter().map(|&p| b.possible_moves_from(black_box(p)).count()).sum::<usize>())
+        .sum());
+}
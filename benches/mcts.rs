@@ -0,0 +1,58 @@
+//! Compares the root-parallel MCTS with the single-threaded one,
+//! both in playouts per second and in playing strength.
+//!
+//! Run with `cargo bench --bench mcts`.
+
+use std::{thread, time::Duration};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use socha_client_2023::{client::GameClientDelegate, game::{Board, State, Team}, logic::OwnLogic, search::TimeManager, selfplay::run_match};
+
+const POSITIONS: usize = 10;
+const TIME_LIMIT: u64 = 200;
+const GAMES: usize = 8;
+
+/// Positions after a few random moves, so that penguins are placed.
+fn positions() -> Vec<State> {
+    let mut rng = StdRng::seed_from_u64(2023);
+    (0..POSITIONS)
+        .map(|i| {
+            let mut state = State::new(Board::generate(&mut rng), 0, [0, 0], None, Team::One);
+            for _ in 0..8 + 2 * i {
+                if let Some(&m) = state.possible_moves().choose(&mut rng) {
+                    state.perform(m);
+                }
+            }
+            state
+        })
+        .collect()
+}
+
+fn main() {
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    let thread_counts: Vec<usize> = [1, 2, 4, 8].into_iter().filter(|&t| t <= cores.max(2)).collect();
+    let positions = positions();
+
+    for &threads in &thread_counts {
+        let mut playouts = 0;
+        for state in &positions {
+            let mut logic = OwnLogic { time_manager: TimeManager::fixed(Duration::from_millis(TIME_LIMIT)), threads, ..Default::default() };
+            logic.request_move(state, state.current_team());
+            playouts += logic.playouts;
+        }
+        let seconds = (POSITIONS as u64 * TIME_LIMIT) as f64 / 1000.;
+        println!("{} thread(s): {:>10.0} playouts/s", threads, playouts as f64 / seconds);
+    }
+
+    for &threads in thread_counts.iter().filter(|&&t| t > 1) {
+        println!("\n{} threads (A) against 1 thread (B), {} ms per move:", threads, TIME_LIMIT);
+        let stats = run_match(
+            GAMES,
+            2023,
+            || OwnLogic { time_manager: TimeManager::fixed(Duration::from_millis(TIME_LIMIT)), threads, ..Default::default() },
+            || OwnLogic { time_manager: TimeManager::fixed(Duration::from_millis(TIME_LIMIT)), ..Default::default() },
+            |_, _, _| (),
+        );
+        println!("{}", stats);
+    }
+}
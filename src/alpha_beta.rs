@@ -0,0 +1,285 @@
+use std::time::Instant;
+
+use log::{info, debug};
+
+use crate::{client::{BestMove, GameClientDelegate}, evaluator::{Evaluator, Territory}, game::{Move, State, Team}, protocol::GameResult, search::{SearchStats, TimeManager, TranspositionTable}};
+
+/// The deepest ply searched.
+pub const MAX_DEPTH: usize = 64;
+/// Evaluations are scaled by this before rounding to integers.
+const SCALE: f64 = 100.;
+/// The number of nodes between checks of the deadline.
+const CHECK_INTERVAL: u64 = 128;
+const INFINITY: i32 = i32::MAX - 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    depth: usize,
+    value: i32,
+    bound: Bound,
+    best_move: Option<Move>,
+}
+
+/// An engine searching with iterative-deepening alpha-beta, using
+/// principal variation search, a transposition table and move
+/// ordering by the table's move, killer moves and fish.
+pub struct AlphaBeta {
+    /// Plans the time to search per move.
+    pub time_manager: TimeManager,
+    /// Evaluates the positions at the end of the search.
+    pub evaluator: Box<dyn Evaluator>,
+    /// The depth reached by the most recent search.
+    pub depth: usize,
+    /// The number of nodes of the most recent search.
+    pub nodes: u64,
+    /// When the pending move request was received.
+    pub move_requested: Option<Instant>,
+    /// The statistics of the most recent move request.
+    pub last_search: Option<SearchStats>,
+    /// The best move of the deepest search completed so far.
+    pub best_move: BestMove,
+    table: TranspositionTable<Entry>,
+    killers: [[Option<Move>; 2]; MAX_DEPTH],
+    deadline: Option<Instant>,
+}
+
+impl Default for AlphaBeta {
+    fn default() -> Self {
+        Self::new(TimeManager::default(), Box::new(Territory))
+    }
+}
+
+impl AlphaBeta {
+    /// Creates an engine using the given time manager and evaluation.
+    pub fn new(time_manager: TimeManager, evaluator: Box<dyn Evaluator>) -> Self {
+        Self {
+            time_manager,
+            evaluator,
+            depth: 0,
+            nodes: 0,
+            move_requested: None,
+            last_search: None,
+            best_move: BestMove::default(),
+            table: TranspositionTable::with_memory(64 << 20),
+            killers: [[None; 2]; MAX_DEPTH],
+            deadline: None,
+        }
+    }
+
+    /// Searches the position until the deadline, returning the best move
+    /// and its value in fish from the view of the team to move.
+    pub fn search(&mut self, state: &State, deadline: Instant) -> Option<(Move, f64)> {
+        self.nodes = 0;
+        self.depth = 0;
+        self.deadline = Some(deadline);
+        self.killers = [[None; 2]; MAX_DEPTH];
+        let mut state = *state;
+        let mut best = None;
+        for depth in 1..=MAX_DEPTH {
+            match self.search_root(&mut state, depth, best.map(|(m, _)| m)) {
+                Some(result) => {
+                    best = Some(result);
+                    self.best_move.set(result.0);
+                    self.depth = depth;
+                    debug!("Depth {}: {} ({}) after {} nodes", depth, result.0, result.1 as f64 / SCALE, self.nodes);
+                    // Every move takes a floe, so the game tree has been searched completely
+                    if (state.board().floes().count_ones() as usize) < depth {
+                        break;
+                    }
+                },
+                None => break,
+            }
+        }
+        best.map(|(m, value)| (m, value as f64 / SCALE))
+    }
+
+    fn search_root(&mut self, state: &mut State, depth: usize, previous: Option<Move>) -> Option<(Move, i32)> {
+        let mut moves = state.possible_moves();
+        self.order(state, &mut moves, previous, 0);
+        let team = state.current_team();
+        let mut alpha = -INFINITY;
+        let mut best = None;
+        for (i, &m) in moves.iter().enumerate() {
+            let undo = state.make(m);
+            let sign = if state.current_team() == team { 1 } else { -1 };
+            let value = if i == 0 {
+                self.search_child(state, sign, depth - 1, 1, -INFINITY, INFINITY)
+            } else {
+                // Null window first, re-searching if the move might be better
+                self.search_child(state, sign, depth - 1, 1, alpha, alpha + 1).and_then(|value| if value > alpha {
+                    self.search_child(state, sign, depth - 1, 1, alpha, INFINITY)
+                } else {
+                    Some(value)
+                })
+            };
+            state.unmake(m, undo);
+            let Some(value) = value else {
+                // Moves that were searched completely can still be trusted
+                return best.filter(|_| i > 0);
+            };
+            if value > alpha {
+                alpha = value;
+                best = Some((m, value));
+            }
+        }
+        if let Some((m, value)) = best {
+            self.table.insert(state.hash(), Entry { depth, value, bound: Bound::Exact, best_move: Some(m) });
+        }
+        best
+    }
+
+    /// Searches a child node within the given window from the parent's view,
+    /// where `sign` tells whether the same team is to move in both.
+    fn search_child(&mut self, state: &mut State, sign: i32, depth: usize, ply: usize, alpha: i32, beta: i32) -> Option<i32> {
+        if sign > 0 {
+            self.negamax(state, depth, ply, alpha, beta)
+        } else {
+            self.negamax(state, depth, ply, -beta, -alpha).map(|v| -v)
+        }
+    }
+
+    /// Principal variation search from the view of the team to move.
+    fn negamax(&mut self, state: &mut State, depth: usize, ply: usize, mut alpha: i32, mut beta: i32) -> Option<i32> {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(CHECK_INTERVAL) && self.deadline.is_some_and(|d| Instant::now() >= d) {
+            return None;
+        }
+
+        let team = state.current_team();
+        if state.is_over() {
+            return Some((state.fish(team) as i32 - state.fish(team.opponent()) as i32) * SCALE as i32);
+        }
+        if depth == 0 || ply >= MAX_DEPTH {
+            return Some((self.evaluator.evaluate(state, team) * SCALE).round() as i32);
+        }
+
+        let hash = state.hash();
+        let mut table_move = None;
+        if let Some(entry) = self.table.get(hash) {
+            table_move = entry.best_move;
+            if entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return Some(entry.value),
+                    Bound::Lower => alpha = alpha.max(entry.value),
+                    Bound::Upper => beta = beta.min(entry.value),
+                }
+                if alpha >= beta {
+                    return Some(entry.value);
+                }
+            }
+        }
+
+        let mut moves = state.possible_moves();
+        self.order(state, &mut moves, table_move, ply);
+        let alpha0 = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        for (i, &m) in moves.iter().enumerate() {
+            let undo = state.make(m);
+            let sign = if state.current_team() == team { 1 } else { -1 };
+            let value = if i == 0 {
+                self.search_child(state, sign, depth - 1, ply + 1, alpha, beta)
+            } else {
+                self.search_child(state, sign, depth - 1, ply + 1, alpha, alpha + 1).and_then(|value| if value > alpha && value < beta {
+                    self.search_child(state, sign, depth - 1, ply + 1, value, beta)
+                } else {
+                    Some(value)
+                })
+            };
+            state.unmake(m, undo);
+            let value = value?;
+            if value > best {
+                best = value;
+                best_move = Some(m);
+            }
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                if Some(m) != self.killers[ply][0] {
+                    self.killers[ply] = [Some(m), self.killers[ply][0]];
+                }
+                break;
+            }
+        }
+
+        let bound = if best <= alpha0 {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.insert(hash, Entry { depth, value: best, bound, best_move });
+        Some(best)
+    }
+
+    /// Sorts the moves by the table's move, then killer moves, then the fish on the target.
+    fn order(&self, state: &State, moves: &mut [Move], table_move: Option<Move>, ply: usize) {
+        let killers = self.killers.get(ply).copied().unwrap_or_default();
+        moves.sort_by_cached_key(|&m| {
+            if Some(m) == table_move {
+                0
+            } else if killers.contains(&Some(m)) {
+                1
+            } else {
+                10 - state.board()[m.to()].fish()
+            }
+        });
+    }
+}
+
+impl GameClientDelegate for AlphaBeta {
+    fn request_move(&mut self, state: &State, _my_team: Team) -> Move {
+        let start = self.move_requested.take().unwrap_or_else(Instant::now);
+        let deadline = self.time_manager.deadline(start, state);
+        let (best_move, value) = self.search(state, deadline)
+            // Not even depth 1 finished in time
+            .unwrap_or_else(|| (state.possible_moves()[0], 0.));
+        let stats = SearchStats { time: start.elapsed(), nodes: self.nodes, depth: Some(self.depth), value: Some(value) };
+        info!("Searched {}", stats);
+        self.last_search = Some(stats);
+        best_move
+    }
+
+    fn on_move_request(&mut self, received: Instant) { self.move_requested = Some(received); }
+
+    fn search_stats(&self) -> Option<SearchStats> { self.last_search }
+
+    fn best_move(&self) -> Option<BestMove> { Some(self.best_move.clone()) }
+
+    fn on_game_end(&mut self, _result: &GameResult) { self.table.clear(); }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use crate::{game::{Board, State, Team}, search::EndgameSolver};
+
+    use super::AlphaBeta;
+
+    #[test]
+    fn test_matches_solver() {
+        // Play randomly until few floes are left, then compare the values
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut state = State::new(Board::generate(&mut rng), 0, [0, 0], None, Team::One);
+        while state.board().floes().count_ones() > 14 || !EndgameSolver::is_applicable(&state) {
+            let m = *state.possible_moves().choose(&mut rng).unwrap();
+            state.perform(m);
+        }
+        let deadline = Instant::now() + Duration::from_secs(30);
+        let team = state.current_team();
+        let solution = EndgameSolver::default().solve(&state, team, deadline).unwrap();
+        let (m, value) = AlphaBeta::default().search(&state, deadline).unwrap();
+        assert_eq!(value, solution.value as f64);
+        assert!(state.validate(m).is_ok());
+    }
+}
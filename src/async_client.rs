@@ -0,0 +1,407 @@
+use std::io::{self, BufReader, Cursor, Read};
+use std::sync::mpsc as std_mpsc;
+use std::time::{Duration, Instant};
+use log::{info, warn, debug, error};
+use quick_xml::events::Event as XmlEvent;
+use quick_xml::Reader;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::{self, JoinHandle};
+use tokio::time;
+use crate::client::{fallback_move, guarded, BestMove, Callback, GameClientDelegate};
+use crate::game::{State, Team};
+use crate::protocol::{Event, EventPayload, GameResult, Request, RequestPayload};
+use crate::search::TimeManager;
+use crate::util::{Element, Error, Result};
+
+/// Makes the bytes read on the runtime available to a blocking XML parser.
+struct ChunkReader {
+    chunks: std_mpsc::Receiver<io::Result<Vec<u8>>>,
+    chunk: Cursor<Vec<u8>>,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = Read::read(&mut self.chunk, buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            match self.chunks.recv() {
+                Ok(chunk) => self.chunk = Cursor::new(chunk?),
+                // The stream is closed
+                Err(_) => return Ok(0),
+            }
+        }
+    }
+}
+
+/// Reads from the stream on the runtime and parses the messages after the
+/// `<protocol>` handshake on a blocking thread, so that parsing large
+/// states doesn't hold up other tasks. The messages come with the time
+/// they were parsed, an error ends them.
+fn spawn_reader(mut read: impl AsyncRead + Unpin + Send + 'static) -> mpsc::UnboundedReceiver<(Instant, Result<Element>)> {
+    let (chunk_sender, chunks) = std_mpsc::channel();
+    tokio::spawn(async move {
+        let mut buf = vec![0; 1 << 14];
+        loop {
+            let chunk = match read.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(read) => Ok(buf[..read].to_vec()),
+                Err(e) => Err(e),
+            };
+            let failed = chunk.is_err();
+            if chunk_sender.send(chunk).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    let (sender, events) = mpsc::unbounded_channel();
+    task::spawn_blocking(move || {
+        let mut reader = Reader::from_reader(BufReader::new(ChunkReader { chunks, chunk: Cursor::new(Vec::new()) }));
+
+        // Read <protocol>
+        let mut buf = Vec::new();
+        let handshake = loop {
+            match reader.read_event(&mut buf) {
+                Ok(XmlEvent::Start(ref start)) if start.name() == b"protocol" => break Ok(()),
+                Ok(XmlEvent::Text(_) | XmlEvent::Decl(_)) => (),
+                Ok(XmlEvent::Eof) => break Err(Error::Eof),
+                Ok(e) => warn!("Got unexpected event {:?}", e),
+                Err(e) => break Err(e.into()),
+            }
+        };
+        if let Err(e) = handshake {
+            let _ = sender.send((Instant::now(), Err(e)));
+            return;
+        }
+
+        loop {
+            let result = Element::read_from(&mut reader);
+            let failed = result.is_err();
+            if sender.send((Instant::now(), result)).is_err() || failed {
+                break;
+            }
+        }
+    });
+    events
+}
+
+/// Writes the message to the stream.
+async fn send(write: &mut (impl AsyncWrite + Unpin), element: &Element) -> Result<()> {
+    write.write_all(element.to_string().as_bytes()).await?;
+    write.flush().await?;
+    debug!("Wrote {}", element);
+    Ok(())
+}
+
+/// A client for a single game on top of tokio, invoking the delegate
+/// like `GameClient` does. The delegate runs on blocking threads, and
+/// if it doesn't pick a legal move by the deadline, a fallback move is
+/// sent so that the game goes on. A late delegate catches up on the
+/// messages once it is done, until then move requests are answered with
+/// fallback moves.
+pub struct AsyncGameClient<D> where D: GameClientDelegate + Send + 'static {
+    /// The delegate if it is idle.
+    delegate: Option<D>,
+    /// The search of the delegate that ran past the deadline.
+    late: Option<JoinHandle<D>>,
+    /// The callbacks that came up during the late search, in order.
+    backlog: Vec<Callback<D>>,
+    reservation_code: Option<String>,
+    move_deadline: Duration,
+}
+
+impl<D> AsyncGameClient<D> where D: GameClientDelegate + Send + 'static {
+    /// Creates a new client using the specified delegate, which moves
+    /// by the default soft timeout of the server.
+    pub fn new(delegate: D, reservation_code: Option<String>) -> Self {
+        Self { delegate: Some(delegate), late: None, backlog: Vec::new(), reservation_code, move_deadline: TimeManager::default().soft_timeout }
+    }
+
+    /// Sets the time after a move request by which a move is sent.
+    pub fn move_deadline(mut self, deadline: Duration) -> Self {
+        self.move_deadline = deadline;
+        self
+    }
+
+    /// Connects to the server via TCP and plays the game, returning its result.
+    pub async fn connect(self, host: &str, port: u16) -> Result<GameResult> {
+        let stream = TcpStream::connect((host, port)).await?;
+        stream.set_nodelay(true)?;
+        info!("Connected to {}:{}", host, port);
+        let (read, write) = stream.into_split();
+        self.run(read, write).await
+    }
+
+    /// Plays the game over the given streams, returning its result.
+    pub async fn run(mut self, read: impl AsyncRead + Unpin + Send + 'static, mut write: impl AsyncWrite + Unpin) -> Result<GameResult> {
+        // Write <protocol> and join
+        write.write_all(b"<protocol>").await?;
+        let join: Element = match self.reservation_code.clone() {
+            Some(reservation_code) => Request::JoinPrepared { reservation_code },
+            None => Request::Join,
+        }.into();
+        info!("Sending join request {}", &join);
+        send(&mut write, &join).await?;
+
+        let mut events = spawn_reader(read);
+        let mut state: Option<State> = None;
+        let mut my_team: Option<Team> = None;
+        let mut game_result = None;
+        let mut pondering = false;
+
+        loop {
+            self.catch_up().await?;
+            let (received, event_xml) = if pondering && self.late.is_none() {
+                match events.try_recv() {
+                    Ok(event) => event,
+                    Err(mpsc::error::TryRecvError::Empty) => {
+                        if let (Some(state), Some(team)) = (state, my_team) {
+                            pondering = self.call("ponder", move |d| d.ponder(&state, team)).await?.unwrap_or(false);
+                        }
+                        continue;
+                    },
+                    Err(mpsc::error::TryRecvError::Disconnected) => return Err(Error::Eof),
+                }
+            } else {
+                events.recv().await.ok_or(Error::Eof)?
+            };
+            let event_xml = event_xml?;
+            pondering = false;
+
+            debug!("Got event {}", event_xml);
+            match Event::try_from(&event_xml) {
+                Ok(Event::Joined { room_id }) => info!("Joined room {}", room_id),
+                Ok(Event::Left { room_id }) => {
+                    info!("Left room {}", room_id);
+                    break;
+                },
+                Ok(Event::Room { room_id, payload }) => {
+                    info!("Got {} in room {}", payload, room_id);
+                    match payload {
+                        EventPayload::Welcome(team) => {
+                            self.notify("on_welcome", move |d| d.on_welcome(team)).await?;
+                            my_team = Some(team);
+                        },
+                        EventPayload::GameResult(result) => {
+                            let ended = result.clone();
+                            self.notify("on_game_end", move |d| d.on_game_end(&ended)).await?;
+                            game_result = Some(result);
+                        },
+                        EventPayload::Memento(new_state) => {
+                            self.notify("on_update_state", move |d| d.on_update_state(&new_state)).await?;
+                            pondering = game_result.is_none() && !new_state.is_terminal()
+                                && my_team.is_some_and(|team| team != new_state.current_team());
+                            state = Some(new_state);
+                        },
+                        EventPayload::MoveRequest => {
+                            let state = state.ok_or_else(|| Error::InvalidState("No state available at move request!".to_owned()))?;
+                            if self.delegate.is_none() {
+                                let fallback = fallback_move(&state, None)?;
+                                error!("Delegate is still searching for its last move, sending {}", fallback);
+                                let request = Request::Room { room_id, payload: RequestPayload::Move(fallback) };
+                                send(&mut write, &request.into()).await?;
+                                continue;
+                            }
+                            let team = state.current_team();
+                            let best_move = self.call("best_move", |d| d.best_move().inspect(BestMove::clear)).await?.flatten();
+                            let deadline = received + self.move_deadline;
+                            let new_move = match self.call_until("request_move", deadline, move |d| {
+                                d.on_move_request(received);
+                                d.request_move(&state, team)
+                            }).await? {
+                                Some(new_move) => match state.validate(new_move) {
+                                    Ok(()) => new_move,
+                                    Err(violation) => {
+                                        let fallback = fallback_move(&state, best_move.as_ref())?;
+                                        error!("Delegate picked illegal move {}: {}, sending {}", new_move, violation, fallback);
+                                        fallback
+                                    },
+                                },
+                                None => {
+                                    let fallback = fallback_move(&state, best_move.as_ref())?;
+                                    error!("Delegate picked no move within {} ms, sending {}", self.move_deadline.as_millis(), fallback);
+                                    fallback
+                                },
+                            };
+                            let request = Request::Room { room_id, payload: RequestPayload::Move(new_move) };
+                            send(&mut write, &request.into()).await?;
+                        },
+                    }
+                },
+                Ok(event @ (Event::Prepared { .. } | Event::Observed { .. })) => {
+                    warn!("Got administrator message {:?}", event);
+                },
+                Err(Error::UnknownElement(element)) => {
+                    warn!("Got unknown tag <{}>: {}", element.name(), element);
+                },
+                Err(Error::ServerError(message)) => {
+                    error!("Server error: {}", message);
+                },
+                Err(e) => {
+                    warn!("Error while parsing event: {:?}", e);
+                },
+            }
+        }
+
+        // Let a late delegate know how the game ended
+        self.join().await?;
+        game_result.ok_or_else(|| Error::InvalidState("Failed to receive game_result".to_string()))
+    }
+
+    /// Takes the delegate back if its late search has finished.
+    async fn catch_up(&mut self) -> Result<()> {
+        if self.late.as_ref().is_some_and(JoinHandle::is_finished) {
+            self.join().await?;
+        }
+        Ok(())
+    }
+
+    /// Waits for the late search to finish if there is one, taking the
+    /// delegate back and making the callbacks it missed in the meantime.
+    async fn join(&mut self) -> Result<()> {
+        if let Some(late) = self.late.take() {
+            if !late.is_finished() {
+                info!("Waiting for the delegate to finish its late search");
+            }
+            self.delegate = Some(late.await.map_err(|e| Error::Custom(format!("Delegate failed: {}", e)))?);
+            for (callback, f) in std::mem::take(&mut self.backlog) {
+                self.call(callback, f).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls back the delegate, or once it has finished its late search.
+    async fn notify(&mut self, callback: &'static str, f: impl FnOnce(&mut D) + Send + 'static) -> Result<()> {
+        self.catch_up().await?;
+        if self.delegate.is_some() {
+            self.call(callback, f).await?;
+        } else {
+            self.backlog.push((callback, Box::new(f)));
+        }
+        Ok(())
+    }
+
+    /// Runs the callback of the delegate on a blocking thread,
+    /// returning none if the delegate panicked.
+    async fn call<T>(&mut self, callback: &'static str, f: impl FnOnce(&mut D) -> T + Send + 'static) -> Result<Option<T>> where T: Send + 'static {
+        self.call_until(callback, None, f).await
+    }
+
+    /// Runs the callback of the idle delegate on a blocking thread, giving
+    /// up waiting for it at the deadline. A late delegate stays busy until
+    /// it finishes.
+    async fn call_until<T>(&mut self, callback: &'static str, deadline: impl Into<Option<Instant>>, f: impl FnOnce(&mut D) -> T + Send + 'static) -> Result<Option<T>> where T: Send + 'static {
+        let mut delegate = self.delegate.take().ok_or_else(|| Error::InvalidState("The delegate is busy".to_owned()))?;
+        let (sender, receiver) = oneshot::channel();
+        let handle = task::spawn_blocking(move || {
+            if let Some(value) = guarded(callback, || f(&mut delegate)) {
+                let _ = sender.send(value);
+            }
+            delegate
+        });
+
+        let value = match deadline.into() {
+            Some(deadline) => match time::timeout_at(deadline.into(), receiver).await {
+                Ok(value) => value.ok(),
+                Err(_) => {
+                    self.late = Some(handle);
+                    return Ok(None);
+                },
+            },
+            None => receiver.await.ok(),
+        };
+        self.delegate = Some(handle.await.map_err(|e| Error::Custom(format!("Delegate failed: {}", e)))?);
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::{Duration, Instant}};
+
+    use tokio::{io::{self, AsyncWriteExt}, sync::mpsc::UnboundedReceiver};
+
+    use crate::{client::{BestMove, GameClientDelegate}, game::{Move, State, Team}, protocol::{GameResult, Request, RequestPayload}, test_util::{end, initial_state, memento, move_request, welcome}, util::{Element, Result}};
+
+    use super::{spawn_reader, AsyncGameClient};
+
+    /// Picks the last move, thinking too long about the first one
+    /// after finding the second move.
+    struct SlowStart {
+        moves: usize,
+        best_move: BestMove,
+    }
+
+    impl GameClientDelegate for SlowStart {
+        fn request_move(&mut self, state: &State, _my_team: Team) -> Move {
+            self.moves += 1;
+            if self.moves == 1 {
+                self.best_move.set(state.possible_moves()[1]);
+                thread::sleep(Duration::from_millis(400));
+            }
+            *state.possible_moves().last().unwrap()
+        }
+
+        fn best_move(&self) -> Option<BestMove> { Some(self.best_move.clone()) }
+    }
+
+    async fn next_request(requests: &mut UnboundedReceiver<(Instant, Result<Element>)>) -> Request {
+        Request::try_from(&requests.recv().await.unwrap().1.unwrap()).unwrap()
+    }
+
+    async fn next_move(requests: &mut UnboundedReceiver<(Instant, Result<Element>)>) -> Move {
+        match next_request(requests).await {
+            Request::Room { payload: RequestPayload::Move(m), .. } => m,
+            request => panic!("Expected a move, got {:?}", request),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deadline() {
+        let (client_stream, server_stream) = io::duplex(1 << 16);
+        let (client_read, client_write) = io::split(client_stream);
+        let (server_read, mut server_write) = io::split(server_stream);
+
+        let server = tokio::spawn(async move {
+            let mut requests = spawn_reader(server_read);
+            let mut state = initial_state();
+
+            assert!(matches!(next_request(&mut requests).await, Request::Join));
+            let start = format!(r#"<protocol><joined roomId="r"/>{}{}{}"#, welcome("r", Team::One), memento("r", &state), move_request("r"));
+            server_write.write_all(start.as_bytes()).await.unwrap();
+            // The delegate is too slow, so the best move found so far is sent
+            let first = next_move(&mut requests).await;
+            assert_eq!(first, state.possible_moves()[1]);
+            state.perform(first);
+            state.perform(state.possible_moves()[0]);
+
+            // The delegate is still searching, so a fallback move is sent right away
+            let requested = Instant::now();
+            server_write.write_all(format!("{}{}", memento("r", &state), move_request("r")).as_bytes()).await.unwrap();
+            let second = next_move(&mut requests).await;
+            assert!(state.validate(second).is_ok());
+            assert!(requested.elapsed() < Duration::from_millis(200));
+            state.perform(second);
+            state.perform(state.possible_moves()[0]);
+
+            // The delegate is done by the next move request
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            server_write.write_all(format!("{}{}", memento("r", &state), move_request("r")).as_bytes()).await.unwrap();
+            let third = next_move(&mut requests).await;
+            assert_eq!(third, *state.possible_moves().last().unwrap());
+            state.perform(third);
+            server_write.write_all(format!("{}</protocol>", end("r", &state)).as_bytes()).await.unwrap();
+            state
+        });
+
+        let client = AsyncGameClient::new(SlowStart { moves: 0, best_move: BestMove::default() }, None).move_deadline(Duration::from_millis(200));
+        let result = client.run(client_read, client_write).await.unwrap();
+        let state = server.await.unwrap();
+        assert_eq!(result, GameResult::scored(&state, None));
+    }
+}
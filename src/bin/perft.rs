@@ -0,0 +1,50 @@
+use std::{fs, io::{self, Read}, time::Instant};
+
+use clap::Parser;
+use socha_client_2023::{game::{Board, State, Team}, search::{perft, divide}};
+
+/// Counts the leaf nodes of the game tree to verify move generation.
+#[derive(Parser, Debug)]
+struct Args {
+    /// A file containing the board in text format, or - for stdin.
+    board: String,
+    /// The depth to count to.
+    #[clap(short, long, default_value_t = 3)]
+    depth: usize,
+    /// The team to move (ONE or TWO).
+    #[clap(short, long, default_value = "ONE")]
+    team: String,
+    /// Lists the counts below each move at the given depth.
+    #[clap(long)]
+    divide: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let text = if args.board == "-" {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text).expect("Could not read board from stdin.");
+        text
+    } else {
+        fs::read_to_string(&args.board).expect("Could not read board file.")
+    };
+    let board: Board = text.parse().expect("Invalid board.");
+    let team: Team = args.team.parse().expect("Invalid team.");
+    let state = State::new(board, 0, [0, 0], None, team);
+
+    print!("{}", board);
+    if args.divide {
+        let counts = divide(&state, args.depth);
+        for (m, count) in &counts {
+            println!("{}: {}", m, count);
+        }
+        println!("Moves: {}, nodes: {}", counts.len(), counts.iter().map(|(_, c)| c).sum::<u64>());
+    } else {
+        for depth in 1..=args.depth {
+            let start = Instant::now();
+            let count = perft(&state, depth);
+            println!("perft({}) = {} ({} ms)", depth, count, start.elapsed().as_millis());
+        }
+    }
+}
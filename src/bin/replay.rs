@@ -0,0 +1,120 @@
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use clap::Parser;
+use simplelog::{SimpleLogger, Config};
+use log::LevelFilter;
+use socha_client_2023::{client::GameClientDelegate, config::{Engine, SearchConfig}, game::Team, logic::OwnLogic, replay::Replay, search::TimeManager};
+
+/// Steps through a replay in the terminal and re-runs engines on its positions.
+#[derive(Parser, Debug)]
+struct Args {
+    /// The replay file, one of ours or the official server's.
+    path: PathBuf,
+    /// The index of the state to start at.
+    #[clap(short, long, default_value_t = 0)]
+    index: usize,
+    /// Searches the starting state and exits instead of stepping through the replay.
+    #[clap(short, long)]
+    search: bool,
+    /// The engine to search with.
+    #[clap(short, long, arg_enum, default_value = "mcts")]
+    engine: Engine,
+    /// The time to search in milliseconds.
+    #[clap(short, long, default_value_t = 1000)]
+    time: u64,
+    /// A config file with the search parameters (see the client).
+    #[clap(short, long)]
+    config: Option<PathBuf>,
+    /// The level to log at.
+    #[clap(short, long, default_value = "Warn")]
+    level: String,
+}
+
+const HELP: &str = "Commands: <enter>/n next, p previous, <index> jump, s search, q quit";
+
+fn print_state(replay: &Replay, index: usize) {
+    let state = &replay.states[index];
+    println!("State {}/{} (turn {}), {} to move", index, replay.states.len() - 1, state.turn(), state.current_team());
+    print!("{}", state.board());
+    println!("Fish: {} {} - {} {}", Team::One, state.fish(Team::One), Team::Two, state.fish(Team::Two));
+    if let Some(m) = replay.move_at(index) {
+        println!("Move: {}", m);
+    }
+    if let Some(stats) = replay.stats[index] {
+        println!("Recorded search: {}", stats);
+    }
+    if index + 1 == replay.states.len() {
+        if let Some(result) = &replay.result {
+            println!("Winner: {}", result.winner().as_ref().map_or("none".to_owned(), |w| w.team().to_string()));
+        }
+    }
+}
+
+fn search(args: &Args, config: &SearchConfig, replay: &Replay, index: usize) {
+    let state = &replay.states[index];
+    if state.is_over() {
+        println!("The game is over");
+        return;
+    }
+    let time_manager = TimeManager::fixed(Duration::from_millis(args.time));
+    let mut logic: Box<dyn GameClientDelegate> = match args.engine {
+        Engine::Mcts => Box::new(OwnLogic { time_manager, ..config.mcts() }),
+        Engine::AlphaBeta => {
+            let mut logic = config.alpha_beta();
+            logic.time_manager = time_manager;
+            Box::new(logic)
+        },
+        Engine::Random => config.engine(Engine::Random),
+    };
+    let start = Instant::now();
+    logic.on_move_request(start);
+    let m = logic.request_move(state, state.current_team());
+    match logic.search_stats() {
+        Some(stats) => println!("{:?} plays {} ({})", args.engine, m, stats),
+        None => println!("{:?} plays {} after {} ms", args.engine, m, start.elapsed().as_millis()),
+    }
+}
+
+fn main() {
+    // Parse command line arguments
+    let args = Args::parse();
+
+    // Set up logging
+    SimpleLogger::init(LevelFilter::from_str(&args.level).expect("Invalid log level."), Config::default()).expect("Could not initialize logger.");
+
+    let replay = Replay::load(&args.path).expect("Could not read replay.");
+    let config = args.config.as_ref().map_or_else(|| Ok(SearchConfig::default()), SearchConfig::load).expect("Could not read config file.");
+    let mut index = args.index.min(replay.states.len() - 1);
+    print_state(&replay, index);
+    if args.search {
+        search(&args, &config, &replay, index);
+        return;
+    }
+
+    println!("{}", HELP);
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        print!("> ");
+        io::stdout().flush().expect("Could not write to stdout.");
+        let Some(Ok(line)) = lines.next() else { break };
+        match line.trim() {
+            "" | "n" => index = (index + 1).min(replay.states.len() - 1),
+            "p" => index = index.saturating_sub(1),
+            "s" => {
+                search(&args, &config, &replay, index);
+                continue;
+            },
+            "q" => break,
+            command => match command.parse::<usize>() {
+                Ok(i) if i < replay.states.len() => index = i,
+                _ => {
+                    println!("{}", HELP);
+                    continue;
+                },
+            },
+        }
+        print_state(&replay, index);
+    }
+}
@@ -0,0 +1,92 @@
+use std::str::FromStr;
+use std::time::Duration;
+use clap::Parser;
+use simplelog::{SimpleLogger, Config};
+use log::{LevelFilter, info};
+use socha_client_2023::{alpha_beta::AlphaBeta, client::GameClientDelegate, config::Engine, evaluator::EvaluatorKind, logic::{OwnLogic, EXPLORATION_CONSTANT}, search::TimeManager, selfplay::{run_match, RandomLogic}};
+
+/// Plays games between two engines without a server and reports statistics.
+#[derive(Parser, Debug)]
+struct Args {
+    /// The number of games, preferably even so that every board is played from both sides.
+    #[clap(short, long, default_value_t = 10)]
+    games: usize,
+    /// The seed for generating boards.
+    #[clap(short, long, default_value_t = 2023)]
+    seed: u64,
+    /// The time per move in milliseconds, which the engines plan their search within.
+    #[clap(short, long, default_value_t = 200)]
+    time_limit: u64,
+    /// Search for the whole time per move instead of planning it by position.
+    #[clap(long)]
+    fixed_time: bool,
+    /// Engine A.
+    #[clap(long, arg_enum, default_value = "mcts")]
+    engine_a: Engine,
+    /// Engine B.
+    #[clap(long, arg_enum, default_value = "mcts")]
+    engine_b: Engine,
+    /// The exploration constant of engine A.
+    #[clap(long, default_value_t = EXPLORATION_CONSTANT)]
+    exploration_a: f64,
+    /// The exploration constant of engine B.
+    #[clap(long, default_value_t = EXPLORATION_CONSTANT)]
+    exploration_b: f64,
+    /// The evaluation function of engine A.
+    #[clap(long, arg_enum, default_value = "territory")]
+    evaluator_a: EvaluatorKind,
+    /// The evaluation function of engine B.
+    #[clap(long, arg_enum, default_value = "territory")]
+    evaluator_b: EvaluatorKind,
+    /// The number of search threads of engine A.
+    #[clap(long, default_value_t = 1)]
+    threads_a: usize,
+    /// The number of search threads of engine B.
+    #[clap(long, default_value_t = 1)]
+    threads_b: usize,
+    /// The level to log at.
+    #[clap(short, long, default_value = "Warn")]
+    level: String,
+}
+
+fn new_engine(engine: Engine, time_manager: TimeManager, evaluator: EvaluatorKind, exploration_constant: f64, threads: usize, seed: u64) -> Box<dyn GameClientDelegate> {
+    match engine {
+        Engine::Mcts => Box::new(OwnLogic { time_manager, evaluator: evaluator.create(), exploration_constant, threads, ..Default::default() }),
+        Engine::AlphaBeta => Box::new(AlphaBeta::new(time_manager, evaluator.create())),
+        Engine::Random => Box::new(RandomLogic::new(seed)),
+    }
+}
+
+fn main() {
+    // Parse command line arguments
+    let args = Args::parse();
+
+    // Set up logging
+    SimpleLogger::init(LevelFilter::from_str(&args.level).expect("Invalid log level."), Config::default()).expect("Could not initialize logger.");
+
+    info!("Running {:?}", args);
+    let time_limit = Duration::from_millis(args.time_limit);
+    let time_manager = if args.fixed_time {
+        TimeManager::fixed(time_limit)
+    } else {
+        // There is no network latency to account for
+        TimeManager { safety_margin: Duration::ZERO, ..TimeManager::new(time_limit) }
+    };
+    let mut seeds_a = (args.seed..).step_by(2);
+    let mut seeds_b = (args.seed + 1..).step_by(2);
+    let stats = run_match(
+        args.games,
+        args.seed,
+        || new_engine(args.engine_a, time_manager.clone(), args.evaluator_a, args.exploration_a, args.threads_a, seeds_a.next().unwrap()),
+        || new_engine(args.engine_b, time_manager.clone(), args.evaluator_b, args.exploration_b, args.threads_b, seeds_b.next().unwrap()),
+        |game, a_team, record| println!(
+            "Game {}: A as {}, fish {} - {}, winner: {}",
+            game + 1,
+            a_team,
+            record.state.fish(a_team),
+            record.state.fish(a_team.opponent()),
+            record.result.winner().as_ref().map_or("none".to_owned(), |w| if w.team() == a_team { "A".to_owned() } else { "B".to_owned() }),
+        ),
+    );
+    println!("{}", stats);
+}
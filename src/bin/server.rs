@@ -0,0 +1,55 @@
+use std::{str::FromStr, time::Duration};
+use clap::Parser;
+use simplelog::{SimpleLogger, Config};
+use log::{LevelFilter, info};
+use socha_client_2023::server::{Server, ServerConfig};
+
+/// Software Challenge 2023 reference server for local games.
+#[derive(Parser, Debug)]
+struct Args {
+    /// The host address to listen on.
+    #[clap(short, long, default_value = "localhost")]
+    host: String,
+    /// The port to listen on.
+    #[clap(short, long, default_value_t = 13050)]
+    port: u16,
+    /// Milliseconds after a move request until a player loses by soft timeout.
+    #[clap(long, default_value_t = 2000)]
+    soft_timeout: u64,
+    /// Milliseconds after a move request until the server stops waiting (hard timeout).
+    #[clap(long, default_value_t = 10000)]
+    hard_timeout: u64,
+    /// The seed for generating boards.
+    #[clap(long)]
+    seed: Option<u64>,
+    /// The password for administrators.
+    #[clap(long, default_value = "examplepassword")]
+    password: String,
+    /// Prepares the given number of rooms and prints their reservation codes.
+    #[clap(long, default_value_t = 0)]
+    prepare: usize,
+    /// The level to log at.
+    #[clap(short, long, default_value = "Info")]
+    level: String,
+}
+
+fn main() {
+    // Parse command line arguments
+    let args = Args::parse();
+
+    // Set up logging
+    SimpleLogger::init(LevelFilter::from_str(&args.level).expect("Invalid log level."), Config::default()).expect("Could not initialize logger.");
+
+    let config = ServerConfig {
+        soft_timeout: Duration::from_millis(args.soft_timeout),
+        hard_timeout: Duration::from_millis(args.hard_timeout),
+        seed: args.seed,
+        password: args.password,
+    };
+    let server = Server::bind((args.host.as_str(), args.port), config).expect("Could not bind server.");
+    for _ in 0..args.prepare {
+        let (room_id, [one, two]) = server.prepare();
+        info!("Prepared room {} with reservations {} (ONE) and {} (TWO)", room_id, one, two);
+    }
+    server.run().expect("Error while running server.");
+}
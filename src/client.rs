@@ -1,10 +1,21 @@
-use std::net::TcpStream;
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::net::{Shutdown, TcpStream};
+use std::path::PathBuf;
 use std::io::{self, BufWriter, BufReader, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, RecvTimeoutError, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 use log::{info, warn, debug, error};
 use quick_xml::events::{Event as XmlEvent, BytesStart};
 use quick_xml::{Reader, Writer};
+use rand::seq::SliceRandom;
 use crate::game::{State, Team, Move};
 use crate::protocol::{Request, Event, GameResult, EventPayload, RequestPayload};
+use crate::replay::{Record, ReplayFormat, ReplayRecorder};
+use crate::search::{SearchStats, TimeManager};
 use crate::util::{Result, Element, Error};
 
 /// A handler that implements the game player's
@@ -20,10 +31,91 @@ pub trait GameClientDelegate {
     /// Invoked when the welcome message is received
     /// with the player's team.
     fn on_welcome(&mut self, _team: Team) {}
+
+    /// Invoked when a move request is received, right before
+    /// `request_move`, with the time the message arrived.
+    fn on_move_request(&mut self, _received: Instant) {}
     
     /// Requests a move from the delegate. This method
     /// should implement the "main" game logic.
     fn request_move(&mut self, state: &State, my_team: Team) -> Move;
+
+    /// Invoked repeatedly while the opponent is to move, so that
+    /// the delegate can search ahead on the opponent's time. Each
+    /// call should only take a few milliseconds, since incoming
+    /// messages are handled in between. Returning false stops
+    /// the calls until the next state arrives.
+    fn ponder(&mut self, _state: &State, _my_team: Team) -> bool { false }
+
+    /// The statistics of the search for the most recent move, if any.
+    fn search_stats(&self) -> Option<SearchStats> { None }
+
+    /// A handle to the best move found so far by a running search, which
+    /// the client sends if `request_move` misses the deadline.
+    fn best_move(&self) -> Option<BestMove> { None }
+}
+
+impl<D> GameClientDelegate for Box<D> where D: GameClientDelegate + ?Sized {
+    fn on_update_state(&mut self, state: &State) { (**self).on_update_state(state) }
+
+    fn on_game_end(&mut self, result: &GameResult) { (**self).on_game_end(result) }
+
+    fn on_welcome(&mut self, team: Team) { (**self).on_welcome(team) }
+
+    fn on_move_request(&mut self, received: Instant) { (**self).on_move_request(received) }
+
+    fn request_move(&mut self, state: &State, my_team: Team) -> Move { (**self).request_move(state, my_team) }
+
+    fn ponder(&mut self, state: &State, my_team: Team) -> bool { (**self).ponder(state, my_team) }
+
+    fn search_stats(&self) -> Option<SearchStats> { (**self).search_stats() }
+
+    fn best_move(&self) -> Option<BestMove> { (**self).best_move() }
+}
+
+/// The best move found so far by a search, shared between the
+/// searching thread and the client's watchdog.
+#[derive(Debug, Clone, Default)]
+pub struct BestMove(Arc<Mutex<Option<Move>>>);
+
+impl BestMove {
+    /// Replaces the best move.
+    pub fn set(&self, best_move: Move) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(best_move);
+    }
+
+    /// Forgets the best move, e.g. before searching another position.
+    pub fn clear(&self) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// The best move, if any.
+    pub fn get(&self) -> Option<Move> {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The move to send in place of the delegate's: the best one found
+/// so far if it is legal, otherwise a random legal move.
+pub(crate) fn fallback_move(state: &State, best_move: Option<&BestMove>) -> Result<Move> {
+    best_move.and_then(BestMove::get)
+        .filter(|&m| state.validate(m).is_ok())
+        .or_else(|| state.possible_moves().choose(&mut rand::thread_rng()).copied())
+        .ok_or_else(|| Error::InvalidState("No move possible at move request!".to_owned()))
+}
+
+/// The message of a caught panic.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload.downcast_ref::<&str>().copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+/// Runs a callback of the delegate, catching and logging a panic in it.
+pub(crate) fn guarded<T>(callback: &str, f: impl FnOnce() -> T) -> Option<T> {
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .map_err(|payload| error!("Delegate panicked in {}: {}", callback, panic_message(&*payload)))
+        .ok()
 }
 
 /// A configuration that determines whether
@@ -34,26 +126,238 @@ pub struct DebugMode {
     pub debug_writer: bool,
 }
 
+/// How the client reconnects after losing the connection to the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// The number of attempts in a row before giving up.
+    pub retries: usize,
+    /// The time to wait before the first attempt, which doubles with every further one.
+    pub backoff: Duration,
+    /// The longest time to wait between attempts.
+    pub max_backoff: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self { retries: 5, backoff: Duration::from_millis(250), max_backoff: Duration::from_secs(4) }
+    }
+}
+
+impl ReconnectPolicy {
+    /// A policy that gives up as soon as the connection is lost.
+    pub fn never() -> Self {
+        Self { retries: 0, ..Default::default() }
+    }
+
+    /// The time to wait after the given number of failed attempts in a row, if any attempts are left.
+    pub fn delay(&self, failures: usize) -> Option<Duration> {
+        (failures < self.retries).then(|| self.backoff.saturating_mul(2u32.saturating_pow(failures as u32)).min(self.max_backoff))
+    }
+}
+
+/// Waits for the thread of a search to hand the delegate back.
+fn join_search<D>(search: JoinHandle<D>) -> Result<D> {
+    search.join().map_err(|e| Error::Custom(format!("Delegate failed: {}", panic_message(&*e))))
+}
+
+/// A callback of the delegate, named for logging.
+pub(crate) type Callback<D> = (&'static str, Box<dyn FnOnce(&mut D) + Send>);
+
+/// A game the client takes part in.
+struct Room<D> {
+    /// The delegate, unless it is still busy with a late search.
+    delegate: Option<D>,
+    /// The search that missed its deadline, which hands the delegate back.
+    late: Option<JoinHandle<D>>,
+    /// The callbacks that came up during the late search, in order.
+    backlog: Vec<Callback<D>>,
+    /// The request that joined the room.
+    join: Request,
+    state: Option<State>,
+    my_team: Option<Team>,
+    game_result: Option<GameResult>,
+    pondering: bool,
+    recorder: Option<ReplayRecorder>,
+}
+
+impl<D> Room<D> {
+    /// Whether the delegate is still busy with a late search.
+    fn searching(&self) -> bool {
+        self.late.as_ref().is_some_and(|late| !late.is_finished())
+    }
+
+    /// Takes the delegate back if its late search has finished.
+    fn catch_up(&mut self) -> Result<()> {
+        if self.searching() {
+            Ok(())
+        } else {
+            self.join()
+        }
+    }
+
+    /// Waits for the late search to finish if there is one, taking the
+    /// delegate back and making the callbacks it missed in the meantime.
+    fn join(&mut self) -> Result<()> {
+        if let Some(late) = self.late.take() {
+            if !late.is_finished() {
+                info!("Waiting for the delegate to finish its late search");
+            }
+            let delegate = self.delegate.insert(join_search(late)?);
+            for (callback, f) in self.backlog.drain(..) {
+                guarded(callback, || f(delegate));
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls back the delegate, or once it has finished its late search.
+    fn notify(&mut self, callback: &'static str, f: impl FnOnce(&mut D) + Send + 'static) -> Result<()> {
+        self.catch_up()?;
+        match &mut self.delegate {
+            Some(delegate) => {
+                guarded(callback, || f(delegate));
+            },
+            None => self.backlog.push((callback, Box::new(f))),
+        }
+        Ok(())
+    }
+}
+
 /// The client which handles XML requests, manages
 /// the game state and invokes the delegate.
-pub struct GameClient<D> where D: GameClientDelegate {
-    delegate: D,
+///
+/// The client can take part in several rooms at once, each with its own
+/// delegate, and in several consecutive games over the same connection.
+/// Delegates of finished games are reused for the next ones. If the
+/// connection drops, the client reconnects according to its policy and
+/// rejoins its rooms, keeping their delegates.
+///
+/// Moves are requested on another thread under a watchdog, which sends a
+/// fallback move if the delegate misses the deadline, panics or picks an
+/// illegal move. A late delegate finishes its search on that thread while
+/// the games go on: The messages of its room are passed on to it once it is
+/// done and its move requests are answered with fallback moves until then.
+pub struct GameClient<D> where D: GameClientDelegate + Send + 'static {
+    /// The delegates not in a room.
+    delegates: Vec<D>,
+    /// Creates delegates once none are left.
+    factory: Option<Box<dyn FnMut() -> D + Send>>,
     debug_mode: DebugMode,
-    reservation_code: Option<String>,
-    // TODO: Add game state
+    games: usize,
+    recording: Option<(PathBuf, ReplayFormat)>,
+    reconnect: ReconnectPolicy,
+    /// The time after a move request by which a move is sent.
+    move_deadline: Duration,
+    /// The join requests without an answer, which are sent again on
+    /// reconnecting, each with the room it rejoins, if any.
+    joins: VecDeque<(Request, Option<String>)>,
+    rooms: HashMap<String, Room<D>>,
+    /// The rooms left while their delegates were still searching.
+    leaving: Vec<Room<D>>,
+    games_started: usize,
+    game_results: Vec<GameResult>,
+    /// Whether the current connection got past the handshake.
+    connected: bool,
 }
 
-impl<D> GameClient<D> where D: GameClientDelegate {
-    /// Creates a new client using the specified delegate.
+impl<D> GameClient<D> where D: GameClientDelegate + Send + 'static {
+    /// Creates a new client for a single game using the specified delegate.
     pub fn new(delegate: D, debug_mode: DebugMode, reservation_code: Option<String>) -> Self {
-        Self { delegate, debug_mode, reservation_code }
+        let join = match reservation_code {
+            Some(code) => Request::JoinPrepared { reservation_code: code },
+            None => Request::Join,
+        };
+        Self::with_delegates(vec![delegate], None, debug_mode).joining(vec![join])
+    }
+
+    /// Creates a new client that creates a delegate per room when needed.
+    pub fn with_factory(factory: impl FnMut() -> D + Send + 'static, debug_mode: DebugMode) -> Self {
+        Self::with_delegates(Vec::new(), Some(Box::new(factory)), debug_mode).joining(vec![Request::Join])
+    }
+
+    fn with_delegates(delegates: Vec<D>, factory: Option<Box<dyn FnMut() -> D + Send>>, debug_mode: DebugMode) -> Self {
+        Self {
+            delegates,
+            factory,
+            debug_mode,
+            games: 1,
+            recording: None,
+            reconnect: ReconnectPolicy::never(),
+            move_deadline: TimeManager::default().soft_timeout,
+            joins: VecDeque::new(),
+            rooms: HashMap::new(),
+            leaving: Vec::new(),
+            games_started: 0,
+            game_results: Vec::new(),
+            connected: false,
+        }
+    }
+
+    /// Replaces the join requests, which are sent at once, joining one room each.
+    pub fn joining(mut self, joins: Vec<Request>) -> Self {
+        self.games_started = joins.len();
+        self.joins = joins.into_iter().map(|join| (join, None)).collect();
+        self
+    }
+
+    /// Sets the total number of games to play. Once a room is left and
+    /// more games are to be played, the client joins any open game.
+    pub fn games(mut self, games: usize) -> Self {
+        self.games = games;
+        self
+    }
+
+    /// Records the games to replay files in the given directory.
+    pub fn record_to(mut self, dir: impl Into<PathBuf>, format: ReplayFormat) -> Self {
+        self.recording = Some((dir.into(), format));
+        self
+    }
+
+    /// Sets how to reconnect after losing the connection. By default, the client never does.
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = policy;
+        self
+    }
+
+    /// Sets the time after a move request by which a move is sent,
+    /// which is the default soft timeout of the server unless set.
+    pub fn move_deadline(mut self, deadline: Duration) -> Self {
+        self.move_deadline = deadline;
+        self
     }
     
     /// Blocks the thread and begins reading XML messages
-    /// from the provided address via TCP.
+    /// from the provided address via TCP. If several games
+    /// are played, this is the result of the first to end.
     pub fn connect(self, host: &str, port: u16) -> Result<GameResult> {
+        self.connect_rooms(host, port).map(|mut results| results.remove(0))
+    }
+
+    /// Like `connect`, but returns the results of all games
+    /// in the order they ended.
+    pub fn connect_rooms(mut self, host: &str, port: u16) -> Result<Vec<GameResult>> {
         let address = format!("{}:{}", host, port);
-        let stream = TcpStream::connect(&address)?;
+        let mut failures = 0;
+        loop {
+            match self.connect_once(&address) {
+                Ok(()) => return self.into_results(),
+                Err(e @ (Error::Io(_) | Error::Eof | Error::Xml(_))) => {
+                    if std::mem::take(&mut self.connected) {
+                        failures = 0;
+                    }
+                    let Some(delay) = self.reconnect.delay(failures) else { return Err(e) };
+                    failures += 1;
+                    warn!("Lost connection ({:?}), reconnecting in {} ms (attempt {} of {})", e, delay.as_millis(), failures, self.reconnect.retries);
+                    thread::sleep(delay);
+                    self.rejoin();
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn connect_once(&mut self, address: &str) -> Result<()> {
+        let stream = TcpStream::connect(address)?;
         info!("Connected to {}", address);
         
         // Begin parsing game messages from the stream.
@@ -62,22 +366,85 @@ impl<D> GameClient<D> where D: GameClientDelegate {
         // of `run_game`.
 
         let mode = &self.debug_mode;
-        let game_result = if mode.debug_reader && !mode.debug_writer {
-            self.run(io::stdin(), stream)?
+        let result = if mode.debug_reader && !mode.debug_writer {
+            self.session(io::stdin(), stream.try_clone()?)
         } else if !mode.debug_reader && mode.debug_writer {
-            self.run(stream, io::stdout())?
+            self.session(stream.try_clone()?, io::stdout())
         } else if mode.debug_reader && mode.debug_writer {
-            self.run(io::stdin(), io::stdout())?
+            self.session(io::stdin(), io::stdout())
         } else {
-            self.run(stream.try_clone()?, stream)?
+            self.session(stream.try_clone()?, stream.try_clone()?)
         };
-        
-        Ok(game_result)
+        if result.is_err() {
+            // Stops the reader thread, which may still be waiting for messages
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        result
+    }
+
+    fn into_results(mut self) -> Result<Vec<GameResult>> {
+        // Let the late delegates know how their games ended
+        self.reclaim(true);
+        if self.game_results.is_empty() {
+            Err(Error::InvalidState("Failed to receive game_result".to_string()))
+        } else {
+            Ok(self.game_results)
+        }
+    }
+
+    /// Prepares rejoining the rooms after losing the connection. Rooms
+    /// whose result arrived count as left, the others are joined again
+    /// with the same reservation or by their id.
+    fn rejoin(&mut self) {
+        for (room_id, mut room) in std::mem::take(&mut self.rooms) {
+            if room.game_result.is_some() {
+                self.leave(room);
+                continue;
+            }
+            let join = match &room.join {
+                Request::JoinPrepared { .. } => room.join.clone(),
+                _ => Request::JoinRoom { room_id: room_id.clone() },
+            };
+            self.joins.push_back((join, Some(room_id.clone())));
+            room.pondering = false;
+            self.rooms.insert(room_id, room);
+        }
+    }
+
+    /// Hands the result of the room over and queues another join
+    /// request if more games are to be played, returning whether it did.
+    fn leave(&mut self, mut room: Room<D>) -> bool {
+        if let Some(Err(e)) = room.recorder.take().map(ReplayRecorder::finish) {
+            warn!("Could not finish replay file: {:?}", e);
+        }
+        self.game_results.extend(room.game_result.take());
+        self.leaving.push(room);
+        self.reclaim(false);
+        if self.games_started < self.games {
+            self.joins.push_back((Request::Join, None));
+            self.games_started += 1;
+            true
+        } else {
+            false
+        }
     }
     
+    /// Takes back the delegates of the rooms left that are done searching,
+    /// or of all of them if waiting for their late searches to finish.
+    fn reclaim(&mut self, wait: bool) {
+        let (done, searching) = std::mem::take(&mut self.leaving).into_iter().partition(|room| wait || !room.searching());
+        self.leaving = searching;
+        for mut room in done {
+            match room.join() {
+                Ok(()) => self.delegates.extend(room.delegate),
+                Err(e) => warn!("Could not reuse the delegate: {:?}", e),
+            }
+        }
+    }
+
     /// Blocks the thread and parses/handles game messages
-    /// from the provided reader.
-    fn run(mut self, read: impl Read, write: impl Write) -> Result<GameResult> {
+    /// from the provided reader until all games are over.
+    fn session(&mut self, read: impl Read + Send + 'static, write: impl Write) -> Result<()> {
         let mut buf = Vec::new();
         let mut reader = Reader::from_reader(BufReader::new(read));
         let mut writer = Writer::new(BufWriter::new(write));
@@ -85,19 +452,19 @@ impl<D> GameClient<D> where D: GameClientDelegate {
         // Write <protocol>
         writer.write_event(XmlEvent::Start(BytesStart::borrowed_name(b"protocol")))?;
         
-        // Send join request
-        let join_xml: Element = match self.reservation_code {
-            Some(code) => Request::JoinPrepared { reservation_code: code.to_owned() },
-            None => Request::Join,
-        }.into();
-        info!("Sending join request {}", &join_xml);
-        join_xml.write_to(&mut writer)?;
+        // Send join requests
+        for (join, _) in &self.joins {
+            let join_xml: Element = join.clone().into();
+            info!("Sending join request {}", &join_xml);
+            join_xml.write_to(&mut writer)?;
+        }
 
         // Read <protocol>
         loop {
             match reader.read_event(&mut buf)? {
                 XmlEvent::Start(ref start) if start.name() == b"protocol" => {
                     info!("Performed handshake");
+                    self.connected = true;
                     break
                 },
                 XmlEvent::Text(_) => (),
@@ -106,43 +473,120 @@ impl<D> GameClient<D> where D: GameClientDelegate {
             }
         }
 
+        // Parse messages on a separate thread, so that the
+        // delegate can ponder until the next one arrives
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || loop {
+            let result = Element::read_from(&mut reader);
+            let failed = result.is_err();
+            if sender.send((Instant::now(), result)).is_err() || failed {
+                break;
+            }
+        });
+
         // Handle events from the server
-        let mut state: Option<State> = None;
-        let mut game_result: Option<GameResult> = None;
-        loop {
-            let event_xml = Element::read_from(&mut reader)?;
+        while !self.joins.is_empty() || !self.rooms.is_empty() {
+            let (received, event_xml) = match events.try_recv() {
+                Ok((received, event_xml)) => (received, event_xml?),
+                Err(TryRecvError::Empty) => {
+                    let ponderable = self.rooms.values_mut().find(|r| r.pondering && !r.searching());
+                    if let Some(room) = ponderable {
+                        room.catch_up()?;
+                        if let (Some(state), Some(team), Some(delegate)) = (room.state, room.my_team, room.delegate.as_mut()) {
+                            room.pondering = guarded("ponder", || delegate.ponder(&state, team)).unwrap_or(false);
+                            continue;
+                        }
+                    }
+                    let (received, event_xml) = events.recv().map_err(|_| Error::Eof)?;
+                    (received, event_xml?)
+                },
+                Err(TryRecvError::Disconnected) => return Err(Error::Eof),
+            };
 
             debug!("Got event {}", event_xml);
             match Event::try_from(&event_xml) {
                 Ok(Event::Joined { room_id }) => {
+                    let (join, rejoined) = self.joins.pop_front().unwrap_or((Request::Join, None));
+                    if let Some(room) = rejoined.and_then(|old_id| self.rooms.remove(&old_id)) {
+                        // Keep the delegate and its search, the next memento brings the state up to date
+                        info!("Rejoined room {}", room_id);
+                        self.rooms.insert(room_id, room);
+                        continue;
+                    }
                     info!("Joined room {}", room_id);
+                    self.reclaim(false);
+                    if self.delegates.is_empty() && self.factory.is_none() {
+                        // No other delegate to play with
+                        self.reclaim(true);
+                    }
+                    let delegate = match (self.delegates.pop(), &mut self.factory) {
+                        (Some(delegate), _) => delegate,
+                        (None, Some(factory)) => factory(),
+                        (None, None) => return Err(Error::InvalidState(format!("No delegate left for room {}", room_id))),
+                    };
+                    let recorder = self.recording.as_ref().and_then(|(dir, format)| match ReplayRecorder::create(dir, &room_id, *format) {
+                        Ok(recorder) => {
+                            info!("Recording to {}", recorder.path().display());
+                            Some(recorder)
+                        },
+                        Err(e) => {
+                            warn!("Could not create replay file: {:?}", e);
+                            None
+                        },
+                    });
+                    self.rooms.insert(room_id, Room { delegate: Some(delegate), late: None, backlog: Vec::new(), join, state: None, my_team: None, game_result: None, pondering: false, recorder });
                 },
                 Ok(Event::Left { room_id }) => {
                     info!("Left room {}", room_id);
-                    break;
+                    let Some(room) = self.rooms.remove(&room_id) else {
+                        warn!("Left unknown room {}", room_id);
+                        continue;
+                    };
+                    if self.leave(room) {
+                        let join_xml: Element = Request::Join.into();
+                        info!("Sending join request {} for game {} of {}", &join_xml, self.games_started, self.games);
+                        join_xml.write_to(&mut writer)?;
+                    }
                 },
                 Ok(Event::Room { room_id, payload }) => {
                     info!("Got {} in room {}", payload, room_id);
+                    let Some(room) = self.rooms.get_mut(&room_id) else {
+                        warn!("Got message for unknown room {}", room_id);
+                        continue;
+                    };
+                    room.pondering = false;
                     match payload {
-                        EventPayload::Welcome(team) => self.delegate.on_welcome(team),
+                        EventPayload::Welcome(team) => {
+                            room.notify("on_welcome", move |d| d.on_welcome(team))?;
+                            room.my_team = Some(team);
+                        },
                         EventPayload::GameResult(result) => {
-                            self.delegate.on_game_end(&result);
-                            game_result = Some(result);
+                            let ended = result.clone();
+                            room.notify("on_game_end", move |d| d.on_game_end(&ended))?;
+                            Self::record(&mut room.recorder, Record::Result(result.clone()));
+                            room.game_result = Some(result);
                         },
                         EventPayload::Memento(new_state) => {
-                            self.delegate.on_update_state(&new_state);
-                            state = Some(new_state);
+                            room.notify("on_update_state", move |d| d.on_update_state(&new_state))?;
+                            Self::record(&mut room.recorder, Record::State(new_state));
+                            room.pondering = room.game_result.is_none() && !new_state.is_terminal()
+                                && room.my_team.is_some_and(|team| team != new_state.current_team());
+                            room.state = Some(new_state);
                         },
                         EventPayload::MoveRequest => {
-                            let state = state.as_ref().ok_or_else(|| Error::InvalidState("No state available at move request!".to_owned()))?;
-                            let team = state.current_team();
-                            let new_move = self.delegate.request_move(state, team);
-                            let request = Request::Room { room_id, payload: RequestPayload::Move(new_move) };
-                            let request_xml = Element::from(request);
-                            request_xml.write_to(&mut writer)?;
+                            let state = room.state.ok_or_else(|| Error::InvalidState("No state available at move request!".to_owned()))?;
+                            let deadline = received + self.move_deadline;
+                            let (new_move, stats) = Self::request_move(room, state, received, deadline, |new_move| {
+                                let request = Request::Room { room_id, payload: RequestPayload::Move(new_move) };
+                                Element::from(request).write_to(&mut writer)
+                            })?;
+                            Self::record(&mut room.recorder, Record::Move { team: state.current_team(), game_move: new_move, stats });
                         },
                     };
                 },
+                Ok(event @ (Event::Prepared { .. } | Event::Observed { .. })) => {
+                    warn!("Got administrator message {:?}", event);
+                },
                 Err(Error::UnknownElement(element)) => {
                     warn!("Got unknown tag <{}>: {}", element.name(), element);
                 },
@@ -155,10 +599,296 @@ impl<D> GameClient<D> where D: GameClientDelegate {
             }
         }
 
-        if let Some(result) = game_result {
-            Ok(result)
-        }else {
-            Err(Error::InvalidState("Failed to receive game_result".to_string()))
+        Ok(())
+    }
+
+    /// Requests a move from the delegate of the room on another thread and passes
+    /// it to the sender. If the delegate panics, misses the deadline or picks an
+    /// illegal move, a fallback move is sent instead, and a late delegate is left
+    /// to finish on its thread. Until it has, move requests are answered with a
+    /// fallback move right away. The move comes with the statistics of its search,
+    /// which a fallback move has none of.
+    fn request_move(room: &mut Room<D>, state: State, received: Instant, deadline: Instant, send: impl FnOnce(Move) -> Result<()>) -> Result<(Move, Option<SearchStats>)> {
+        let team = state.current_team();
+        room.catch_up()?;
+        let Some(mut delegate) = room.delegate.take() else {
+            let fallback = fallback_move(&state, None)?;
+            error!("Delegate is still searching for its last move, sending {}", fallback);
+            send(fallback)?;
+            return Ok((fallback, None));
+        };
+        let best_move = delegate.best_move();
+        if let Some(best_move) = &best_move {
+            best_move.clear();
+        }
+        let (sender, receiver) = mpsc::channel();
+        let search = thread::spawn(move || {
+            if let Some(new_move) = guarded("request_move", || {
+                delegate.on_move_request(received);
+                delegate.request_move(&state, team)
+            }) {
+                let _ = sender.send((new_move, delegate.search_stats()));
+            }
+            delegate
+        });
+
+        let received_move = receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()));
+        if let Err(RecvTimeoutError::Timeout) = received_move {
+            room.late = Some(search);
+        } else {
+            // The search is over once it sent its move or panicked
+            room.delegate = Some(join_search(search)?);
         }
+
+        let (new_move, stats) = match received_move {
+            Ok((new_move, stats)) => match state.validate(new_move) {
+                Ok(()) => (new_move, stats),
+                Err(violation) => {
+                    let fallback = fallback_move(&state, best_move.as_ref())?;
+                    error!("Delegate picked illegal move {}: {}, sending {}", new_move, violation, fallback);
+                    (fallback, None)
+                },
+            },
+            Err(e) => {
+                let fallback = fallback_move(&state, best_move.as_ref())?;
+                match e {
+                    RecvTimeoutError::Timeout => error!("Delegate picked no move within {} ms, sending {}", (deadline - received).as_millis(), fallback),
+                    RecvTimeoutError::Disconnected => error!("Delegate picked no move, sending {}", fallback),
+                }
+                (fallback, None)
+            },
+        };
+        send(new_move)?;
+        Ok((new_move, stats))
+    }
+
+    /// Writes the record to the replay file if recording, which must not interrupt the game.
+    fn record(recorder: &mut Option<ReplayRecorder>, record: Record) {
+        if let Some(Err(e)) = recorder.as_mut().map(|r| r.record(&record)) {
+            warn!("Could not record to replay file: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::{self, BufReader, Write}, net::{Shutdown, TcpListener, TcpStream}, sync::{atomic::{AtomicUsize, Ordering}, Arc}, thread, time::{Duration, Instant}};
+
+    use quick_xml::Reader;
+
+    use crate::{game::{Move, State, Team}, protocol::Request, test_util::{end, initial_state, memento, move_request, read_handshake, read_move, read_request, welcome, Cheater, FirstMove, Sleeper}};
+
+    use super::{BestMove, DebugMode, GameClient, GameClientDelegate, ReconnectPolicy};
+
+    /// Counts its moves, so that a replaced delegate would show.
+    struct CountingMoves {
+        moves: usize,
+        total: Arc<AtomicUsize>,
+    }
+
+    impl GameClientDelegate for CountingMoves {
+        fn request_move(&mut self, state: &State, _my_team: Team) -> Move {
+            self.moves += 1;
+            self.total.store(self.moves, Ordering::Relaxed);
+            state.possible_moves()[0]
+        }
+    }
+
+    /// Panics at the first move and is too slow for the second.
+    struct Unreliable {
+        moves: usize,
+        best_move: BestMove,
+    }
+
+    impl GameClientDelegate for Unreliable {
+        fn request_move(&mut self, state: &State, _my_team: Team) -> Move {
+            self.moves += 1;
+            if self.moves == 1 {
+                panic!("Lost track of the game");
+            }
+            self.best_move.set(state.possible_moves()[1]);
+            thread::sleep(Duration::from_millis(300));
+            state.possible_moves()[2]
+        }
+
+        fn best_move(&self) -> Option<BestMove> { Some(self.best_move.clone()) }
+    }
+
+    /// Accepts a connection to the fake server, returning the join request.
+    fn accept(listener: &TcpListener) -> (Request, Reader<BufReader<TcpStream>>, TcpStream) {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = Reader::from_reader(BufReader::new(stream.try_clone().unwrap()));
+        read_handshake(&mut reader);
+        let join = read_request(&mut reader);
+        (join, reader, stream)
+    }
+
+    #[test]
+    fn test_rooms() {
+        let state = initial_state();
+        let game = |id: &str, team: Team| [welcome(id, team), memento(id, &state)].concat();
+        // Two rooms at once, then another game after the first one ended
+        let messages = [
+            r#"<protocol><joined roomId="a"/><joined roomId="b"/>"#.to_owned(),
+            game("a", Team::One),
+            game("b", Team::Two),
+            move_request("a"),
+            end("a", &state),
+            r#"<joined roomId="c"/>"#.to_owned(),
+            game("c", Team::One),
+            end("b", &state),
+            end("c", &state),
+        ].concat();
+
+        let created = Arc::new(AtomicUsize::new(0));
+        let counter = created.clone();
+        let factory = move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+            FirstMove
+        };
+        let debug_mode = DebugMode { debug_reader: false, debug_writer: false };
+        let mut client = GameClient::with_factory(factory, debug_mode)
+            .joining(vec![Request::JoinRoom { room_id: "a".to_owned() }, Request::JoinRoom { room_id: "b".to_owned() }])
+            .games(3);
+        client.session(io::Cursor::new(messages.into_bytes()), io::sink()).unwrap();
+        assert_eq!(client.into_results().unwrap().len(), 3);
+        // The delegate of the first game is reused for the third one
+        assert_eq!(created.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut state = initial_state();
+
+        let server = thread::spawn(move || {
+            // The connection drops after the first move
+            let (join, mut reader, mut stream) = accept(&listener);
+            assert!(matches!(join, Request::JoinPrepared { ref reservation_code } if reservation_code == "code"));
+            write!(stream, r#"<protocol><joined roomId="r"/>{}{}{}"#, welcome("r", Team::One), memento("r", &state), move_request("r")).unwrap();
+            state.perform(read_move(&mut reader));
+            stream.shutdown(Shutdown::Both).unwrap();
+
+            // The client rejoins with the same reservation and catches up with the opponent's move
+            let (join, mut reader, mut stream) = accept(&listener);
+            assert!(matches!(join, Request::JoinPrepared { ref reservation_code } if reservation_code == "code"));
+            state.perform(state.possible_moves()[0]);
+            write!(stream, r#"<protocol><joined roomId="r"/>{}{}"#, memento("r", &state), move_request("r")).unwrap();
+            let second = read_move(&mut reader);
+            assert!(state.validate(second).is_ok());
+            state.perform(second);
+            write!(stream, "{}</protocol>", end("r", &state)).unwrap();
+        });
+
+        let moves = Arc::new(AtomicUsize::new(0));
+        let delegate = CountingMoves { moves: 0, total: moves.clone() };
+        let debug_mode = DebugMode { debug_reader: false, debug_writer: false };
+        let policy = ReconnectPolicy { retries: 2, backoff: Duration::from_millis(10), ..Default::default() };
+        let client = GameClient::new(delegate, debug_mode, Some("code".to_owned())).reconnect(policy);
+        let results = client.connect_rooms("127.0.0.1", port).unwrap();
+        server.join().unwrap();
+        assert_eq!(results.len(), 1);
+        // The same delegate made both moves
+        assert_eq!(moves.load(Ordering::Relaxed), 2);
+        assert!(ReconnectPolicy::never().delay(0).is_none());
+    }
+
+    #[test]
+    fn test_watchdog() {
+        let mut state = initial_state();
+        let first = state;
+        state.perform(state.possible_moves()[0]);
+        state.perform(state.possible_moves()[0]);
+        let messages = [
+            r#"<protocol><joined roomId="r"/>"#.to_owned(),
+            welcome("r", Team::One),
+            memento("r", &first),
+            move_request("r"),
+            memento("r", &state),
+            move_request("r"),
+            end("r", &state),
+        ].concat();
+
+        let delegate = Unreliable { moves: 0, best_move: BestMove::default() };
+        let debug_mode = DebugMode { debug_reader: false, debug_writer: false };
+        let mut client = GameClient::new(delegate, debug_mode, None).move_deadline(Duration::from_millis(100));
+        let mut written = Vec::new();
+        client.session(io::Cursor::new(messages.into_bytes()), &mut written).unwrap();
+        assert_eq!(client.into_results().unwrap().len(), 1);
+
+        let mut reader = Reader::from_reader(&written[..]);
+        read_handshake(&mut reader);
+        assert!(matches!(read_request(&mut reader), Request::Join));
+        let moves: Vec<Move> = (0..2).map(|_| read_move(&mut reader)).collect();
+        // A random move after the panic, then the best move found before the deadline
+        assert!(first.validate(moves[0]).is_ok());
+        assert_eq!(moves[1], state.possible_moves()[1]);
+    }
+
+    #[test]
+    fn test_illegal_move() {
+        let state = initial_state();
+        let messages = [
+            r#"<protocol><joined roomId="r"/>"#.to_owned(),
+            welcome("r", Team::One),
+            memento("r", &state),
+            move_request("r"),
+            end("r", &state),
+        ].concat();
+
+        let debug_mode = DebugMode { debug_reader: false, debug_writer: false };
+        let mut client = GameClient::new(Cheater::default(), debug_mode, None);
+        let mut written = Vec::new();
+        client.session(io::Cursor::new(messages.into_bytes()), &mut written).unwrap();
+
+        let mut reader = Reader::from_reader(&written[..]);
+        read_handshake(&mut reader);
+        read_request(&mut reader);
+        // The best move instead of the illegal one
+        assert_eq!(read_move(&mut reader), state.possible_moves()[1]);
+    }
+
+    #[test]
+    fn test_late_search() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let state = initial_state();
+        let deadline = Duration::from_millis(100);
+
+        let server = thread::spawn(move || {
+            let start = |id: &str| [welcome(id, Team::One), memento(id, &state)].concat();
+
+            let (_, mut reader, mut stream) = accept(&listener);
+            read_request(&mut reader);
+            write!(stream, r#"<protocol><joined roomId="a"/><joined roomId="b"/>{}{}{}"#, start("a"), start("b"), move_request("a")).unwrap();
+            // The delegate of room a is still searching while room a gets a memento and room b asks for a move
+            assert!(state.validate(read_move(&mut reader)).is_ok());
+            let requested = Instant::now();
+            write!(stream, "{}{}", memento("a", &state), move_request("b")).unwrap();
+            assert_eq!(read_move(&mut reader), state.possible_moves()[0]);
+            assert!(requested.elapsed() < deadline);
+            // Room a gets a fallback move right away
+            let requested = Instant::now();
+            write!(stream, "{}", move_request("a")).unwrap();
+            assert!(state.validate(read_move(&mut reader)).is_ok());
+            assert!(requested.elapsed() < deadline);
+            write!(stream, "{}{}</protocol>", end("a", &state), end("b", &state)).unwrap();
+        });
+
+        // The first delegate outlives the deadlines of both move requests
+        let mut delays = vec![Duration::ZERO, deadline * 3];
+        let ended = Arc::new(AtomicUsize::new(0));
+        let counter = ended.clone();
+        let factory = move || Sleeper { delay: delays.pop().unwrap(), ended: counter.clone() };
+        let debug_mode = DebugMode { debug_reader: false, debug_writer: false };
+        let client = GameClient::with_factory(factory, debug_mode)
+            .joining(vec![Request::JoinRoom { room_id: "a".to_owned() }, Request::JoinRoom { room_id: "b".to_owned() }])
+            .move_deadline(deadline);
+        let results = client.connect_rooms("127.0.0.1", port).unwrap();
+        server.join().unwrap();
+        assert_eq!(results.len(), 2);
+        // The late delegate still learns how its game ended
+        assert_eq!(ended.load(Ordering::Relaxed), 2);
     }
 }
@@ -0,0 +1,173 @@
+use std::{fmt, fs, path::Path, str::FromStr, time::Duration};
+
+use clap::ArgEnum;
+
+use crate::{alpha_beta::AlphaBeta, client::GameClientDelegate, evaluator::EvaluatorKind, logic::{OwnLogic, EXPLORATION_CONSTANT, TREE_MEMORY}, search::TimeManager, selfplay::RandomLogic, util::{Error, Result}};
+
+/// The engines to play with, e.g. for picking one on the command line.
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    Mcts,
+    AlphaBeta,
+    /// Plays random moves.
+    Random,
+}
+
+/// The tunable parameters of the engines, which can be read from a config
+/// file with one `key = value` per line (i.e. a flat TOML file), e.g.:
+///
+/// ```text
+/// # Tournament settings
+/// soft_timeout = 2000
+/// threads = 4
+/// evaluator = "voronoi"
+/// ```
+///
+/// Durations are given in milliseconds and memory in MiB.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchConfig {
+    /// The server's soft timeout per move.
+    pub soft_timeout: Duration,
+    /// The time kept free for network latency.
+    pub safety_margin: Duration,
+    /// The weight of exploration in the UCB1 formula (MCTS only).
+    pub exploration_constant: f64,
+    /// The number of threads to search with (MCTS only).
+    pub threads: usize,
+    /// The evaluation function of the engine.
+    pub evaluator: EvaluatorKind,
+    /// Seeds the tie-breaking between equally good moves (MCTS only).
+    pub seed: Option<u64>,
+    /// The memory the game tree may use in MiB (MCTS only).
+    pub tree_memory: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        let time_manager = TimeManager::default();
+        Self {
+            soft_timeout: time_manager.soft_timeout,
+            safety_margin: time_manager.safety_margin,
+            exploration_constant: EXPLORATION_CONSTANT,
+            threads: 1,
+            evaluator: EvaluatorKind::Territory,
+            seed: None,
+            tree_memory: TREE_MEMORY >> 20,
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Reads the config file, using the defaults for missing keys.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Sets the parameter with the given key from its textual value.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let value = value.trim_matches('"');
+        match key {
+            "soft_timeout" => self.soft_timeout = Duration::from_millis(value.parse()?),
+            "safety_margin" => self.safety_margin = Duration::from_millis(value.parse()?),
+            "exploration_constant" => self.exploration_constant = value.parse()?,
+            "threads" => self.threads = value.parse()?,
+            "evaluator" => self.evaluator = value.parse()?,
+            "seed" => self.seed = Some(value.parse()?),
+            "tree_memory" => self.tree_memory = value.parse()?,
+            _ => return Err(Error::UnknownVariant(format!("Unknown config key {}", key))),
+        }
+        Ok(())
+    }
+
+    /// The time manager with the configured timeouts.
+    pub fn time_manager(&self) -> TimeManager {
+        TimeManager { soft_timeout: self.soft_timeout, safety_margin: self.safety_margin, ..Default::default() }
+    }
+
+    /// Creates the MCTS engine.
+    pub fn mcts(&self) -> OwnLogic {
+        OwnLogic {
+            time_manager: self.time_manager(),
+            exploration_constant: self.exploration_constant,
+            threads: self.threads,
+            evaluator: self.evaluator.create(),
+            seed: self.seed,
+            tree_memory: self.tree_memory << 20,
+            ..Default::default()
+        }
+    }
+
+    /// Creates the alpha-beta engine.
+    pub fn alpha_beta(&self) -> AlphaBeta {
+        AlphaBeta::new(self.time_manager(), self.evaluator.create())
+    }
+
+    /// Creates the given engine, a random one seeded by the configured seed if any.
+    pub fn engine(&self, engine: Engine) -> Box<dyn GameClientDelegate + Send> {
+        match engine {
+            Engine::Mcts => Box::new(self.mcts()),
+            Engine::AlphaBeta => Box::new(self.alpha_beta()),
+            Engine::Random => Box::new(RandomLogic::new(self.seed.unwrap_or_else(rand::random))),
+        }
+    }
+}
+
+impl FromStr for SearchConfig {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut config = Self::default();
+        for line in s.lines().map(|l| l.split('#').next().unwrap().trim()).filter(|l| !l.is_empty()) {
+            let (key, value) = line.split_once('=').ok_or_else(|| Error::Custom(format!("Invalid config line {}", line)))?;
+            config.set(key.trim(), value.trim())?;
+        }
+        Ok(config)
+    }
+}
+
+impl fmt::Display for SearchConfig {
+    /// Writes the config in the format of the config file.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "soft_timeout = {}", self.soft_timeout.as_millis())?;
+        writeln!(f, "safety_margin = {}", self.safety_margin.as_millis())?;
+        writeln!(f, "exploration_constant = {}", self.exploration_constant)?;
+        writeln!(f, "threads = {}", self.threads)?;
+        writeln!(f, "evaluator = \"{}\"", self.evaluator)?;
+        if let Some(seed) = self.seed {
+            writeln!(f, "seed = {}", seed)?;
+        }
+        write!(f, "tree_memory = {}", self.tree_memory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use indoc::indoc;
+
+    use crate::evaluator::EvaluatorKind;
+
+    use super::SearchConfig;
+
+    #[test]
+    fn test_parse() {
+        let config: SearchConfig = indoc! {r#"
+            # Tournament settings
+            soft_timeout = 1800
+            threads = 4 # one per core
+            evaluator = "voronoi"
+            seed = 42
+        "#}.parse().unwrap();
+        assert_eq!(config.soft_timeout, Duration::from_millis(1800));
+        assert_eq!(config.threads, 4);
+        assert_eq!(config.evaluator, EvaluatorKind::Voronoi);
+        assert_eq!(config.seed, Some(42));
+        assert_eq!(config.safety_margin, SearchConfig::default().safety_margin);
+        assert_eq!(config.to_string().parse::<SearchConfig>().unwrap(), config);
+
+        assert!("depth = 3".parse::<SearchConfig>().is_err());
+        assert!("threads".parse::<SearchConfig>().is_err());
+        assert!("threads = many".parse::<SearchConfig>().is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use std::{fmt, str::FromStr};
+
+use clap::ArgEnum;
+
+use crate::{game::{Bitboard, BitIter, State, Team, MAX_FISH, NEIGHBORS}, util::{Error, Result}};
+
+/// Estimates how good a position is for a team, in fish
+/// that the team will end up ahead of its opponent.
+pub trait Evaluator: Send + Sync {
+    /// Evaluates the state from the view of the given team.
+    fn evaluate(&self, state: &State, team: Team) -> f64;
+}
+
+impl<E> Evaluator for Box<E> where E: Evaluator + ?Sized {
+    fn evaluate(&self, state: &State, team: Team) -> f64 { (**self).evaluate(state, team) }
+}
+
+/// The difference of the fish collected so far.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FishDifference;
+
+impl Evaluator for FishDifference {
+    fn evaluate(&self, state: &State, team: Team) -> f64 {
+        state.fish(team) as f64 - state.fish(team.opponent()) as f64
+    }
+}
+
+/// Heuristic to predict the outcoming score of a game using Breadth-First-Search:
+/// every floe counts for the team that reaches it in fewer moves (see `Board::territory`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Territory;
+
+impl Evaluator for Territory {
+    fn evaluate(&self, state: &State, team: Team) -> f64 {
+        let territory = state.board().territory();
+        (state.fish(team) + territory.fish(team)) as f64 - (state.fish(team.opponent()) + territory.fish(team.opponent())) as f64
+    }
+}
+
+/// Like `Territory`, but measures the distance to the floes in steps to
+/// neighboring floes instead of moves, which is cheaper to compute.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Voronoi;
+
+impl Evaluator for Voronoi {
+    fn evaluate(&self, state: &State, team: Team) -> f64 {
+        let board = state.board();
+        let floes = board.floes();
+        let fish = |fields: Bitboard| -> f64 {
+            (1..=MAX_FISH).map(|n| (n as u32 * (board.fish_mask(n) & fields).count_ones()) as f64).sum()
+        };
+        let grow = |fields: Bitboard| BitIter::ascending(fields).fold(0, |acc, i| acc | NEIGHBORS[i]) & floes;
+
+        // Grow both teams' regions step by step, floes reached at the same time stay neutral
+        let mut ours = board.penguin_mask(team);
+        let mut theirs = board.penguin_mask(team.opponent());
+        let mut claimed = ours | theirs;
+        let mut value = state.fish(team) as f64 - state.fish(team.opponent()) as f64;
+        while ours | theirs != 0 {
+            let next_ours = grow(ours) & !claimed;
+            let next_theirs = grow(theirs) & !claimed;
+            let contested = next_ours & next_theirs;
+            ours = next_ours & !contested;
+            theirs = next_theirs & !contested;
+            claimed |= next_ours | next_theirs;
+            value += fish(ours) - fish(theirs);
+        }
+        value
+    }
+}
+
+/// The fish difference plus the difference in possible moves, weighted
+/// by how many fish a possible move is worth.
+#[derive(Debug, Clone, Copy)]
+pub struct Mobility {
+    pub weight: f64,
+}
+
+impl Default for Mobility {
+    fn default() -> Self {
+        Self { weight: 0.25 }
+    }
+}
+
+impl Evaluator for Mobility {
+    fn evaluate(&self, state: &State, team: Team) -> f64 {
+        let moves = |team: Team| state.board().penguins()
+            .filter(|&(_, t)| t == team)
+            .map(|(c, _)| state.board().possible_moves_from(c).count())
+            .sum::<usize>() as f64;
+        FishDifference.evaluate(state, team) + self.weight * (moves(team) - moves(team.opponent()))
+    }
+}
+
+/// The built-in evaluators, e.g. for picking one on the command line.
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvaluatorKind {
+    #[clap(name = "fish")]
+    FishDifference,
+    Territory,
+    Voronoi,
+    Mobility,
+}
+
+impl EvaluatorKind {
+    /// Creates the evaluator with its default settings.
+    pub fn create(self) -> Box<dyn Evaluator> {
+        match self {
+            Self::FishDifference => Box::new(FishDifference),
+            Self::Territory => Box::new(Territory),
+            Self::Voronoi => Box::new(Voronoi),
+            Self::Mobility => Box::new(Mobility::default()),
+        }
+    }
+}
+
+impl fmt::Display for EvaluatorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_possible_value().expect("No evaluator is hidden").get_name())
+    }
+}
+
+impl FromStr for EvaluatorKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        <Self as ArgEnum>::from_str(s, false).map_err(|_| Error::UnknownVariant(format!("Unknown evaluator {}", s)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::{game::{Board, State, Team}, util::Element};
+
+    use super::{Evaluator, EvaluatorKind, FishDifference, Mobility, Territory, Voronoi};
+
+    /// Team One walls off the upper rows, Team Two the lower ones.
+    const BOARD: &str = indoc! {r#"
+        <board>
+          <list><field>2</field><field>2</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field></list>
+          <list><field>ONE</field><field>ONE</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field></list>
+          <list><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field></list>
+          <list><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field></list>
+          <list><field>ONE</field><field>ONE</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field></list>
+          <list><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field></list>
+          <list><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>TWO</field><field>TWO</field></list>
+          <list><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>TWO</field><field>TWO</field><field>1</field></list>
+        </board>
+    "#};
+
+    #[test]
+    fn test_evaluators() {
+        let board = Board::try_from(&BOARD.parse::<Element>().unwrap()).unwrap();
+        let state = State::new(board, 8, [3, 5], None, Team::One);
+
+        assert_eq!(FishDifference.evaluate(&state, Team::One), -2.);
+        // One can reach 2 + 2 fish, Two only 1
+        assert_eq!(Territory.evaluate(&state, Team::One), (3. + 4.) - (5. + 1.));
+        assert_eq!(Voronoi.evaluate(&state, Team::Two), (5. + 1.) - (3. + 4.));
+        assert!(Mobility::default().evaluate(&state, Team::One) > FishDifference.evaluate(&state, Team::One));
+
+        assert_eq!(EvaluatorKind::FishDifference.to_string(), "fish");
+        for kind in [EvaluatorKind::FishDifference, EvaluatorKind::Territory, EvaluatorKind::Voronoi, EvaluatorKind::Mobility] {
+            assert_eq!(kind.to_string().parse::<EvaluatorKind>().unwrap(), kind);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use super::{Vec2, Doubled, BOARD_FIELDS, BOARD_SIZE};
+
+/// A set of fields, one bit per field index (see `Board::index_for`).
+pub type Bitboard = u64;
+
+/// The number of directions on the hex board.
+pub const DIRECTION_COUNT: usize = Vec2::<Doubled>::DIRECTIONS.len();
+
+/// The rays from every field along each of `Vec2::<Doubled>::DIRECTIONS`,
+/// i.e. the fields a penguin could slide to on a board without obstacles.
+pub static RAYS: [[Bitboard; DIRECTION_COUNT]; BOARD_FIELDS] = rays();
+
+const fn rays() -> [[Bitboard; DIRECTION_COUNT]; BOARD_FIELDS] {
+    let mut rays = [[0; DIRECTION_COUNT]; BOARD_FIELDS];
+    let mut i = 0;
+    while i < BOARD_FIELDS {
+        let y = (i / BOARD_SIZE) as i32;
+        let x = (i % BOARD_SIZE) as i32 * 2 + y % 2;
+        let mut d = 0;
+        while d < DIRECTION_COUNT {
+            let direction = Vec2::<Doubled>::DIRECTIONS[d];
+            let mut rx = x + direction.x;
+            let mut ry = y + direction.y;
+            // Same bounds check as `Board::in_bounds`, written out since it has to be const
+            while rx >= 0 && rx / 2 < BOARD_SIZE as i32 && ry >= 0 && ry < BOARD_SIZE as i32 {
+                rays[i][d] |= 1 << (ry as usize * BOARD_SIZE + rx as usize / 2);
+                rx += direction.x;
+                ry += direction.y;
+            }
+            d += 1;
+        }
+        i += 1;
+    }
+    rays
+}
+
+/// The adjacent fields of every field.
+pub static NEIGHBORS: [Bitboard; BOARD_FIELDS] = neighbors();
+
+const fn neighbors() -> [Bitboard; BOARD_FIELDS] {
+    let rays = rays();
+    let mut neighbors = [0; BOARD_FIELDS];
+    let mut i = 0;
+    while i < BOARD_FIELDS {
+        let mut d = 0;
+        while d < DIRECTION_COUNT {
+            // The nearest field of each ray
+            let ray = rays[i][d];
+            if ray != 0 {
+                neighbors[i] |= if ascending(d) { ray & ray.wrapping_neg() } else { 1 << (63 - ray.leading_zeros()) };
+            }
+            d += 1;
+        }
+        i += 1;
+    }
+    neighbors
+}
+
+/// Whether walking in the given direction increases the field index.
+#[inline]
+pub const fn ascending(direction: usize) -> bool {
+    let v = Vec2::<Doubled>::DIRECTIONS[direction];
+    v.y > 0 || (v.y == 0 && v.x > 0)
+}
+
+/// The fields reachable by sliding from the given field index in the given
+/// direction, stopping in front of the first field that is not `free`.
+#[inline]
+pub fn slide(index: usize, direction: usize, free: Bitboard) -> Bitboard {
+    let ray = RAYS[index][direction];
+    let blockers = ray & !free;
+    if blockers == 0 {
+        return ray;
+    }
+    let blocker = if ascending(direction) {
+        blockers.trailing_zeros()
+    } else {
+        63 - blockers.leading_zeros()
+    } as usize;
+    ray & !RAYS[blocker][direction] & !(1 << blocker)
+}
+
+/// An iterator over the field indices of a bitboard.
+#[derive(Debug, Clone, Copy)]
+pub struct BitIter {
+    bits: Bitboard,
+    descending: bool,
+}
+
+impl BitIter {
+    /// Iterates the indices from lowest to highest.
+    pub const fn ascending(bits: Bitboard) -> Self {
+        Self { bits, descending: false }
+    }
+
+    /// Iterates the indices from highest to lowest.
+    pub const fn descending(bits: Bitboard) -> Self {
+        Self { bits, descending: true }
+    }
+
+    /// Iterates the indices of a ray in the given direction,
+    /// nearest field first.
+    pub const fn along(bits: Bitboard, direction: usize) -> Self {
+        Self { bits, descending: !ascending(direction) }
+    }
+}
+
+impl Iterator for BitIter {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        if self.bits == 0 {
+            return None;
+        }
+        let index = if self.descending {
+            63 - self.bits.leading_zeros()
+        } else {
+            self.bits.trailing_zeros()
+        } as usize;
+        self.bits &= !(1 << index);
+        Some(index)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.bits.count_ones() as usize;
+        (count, Some(count))
+    }
+}
+
+impl ExactSizeIterator for BitIter {}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{Board, Vec2, Doubled, Direct};
+
+    use super::{RAYS, NEIGHBORS, slide, BitIter};
+
+    #[test]
+    fn test_rays_match_stepping() {
+        for (i, rays) in RAYS.iter().enumerate() {
+            let from: Vec2<Doubled> = Board::coords_for(i).into();
+            for (d, &v) in Vec2::<Doubled>::DIRECTIONS.iter().enumerate() {
+                let stepped: Vec<usize> = (1..8)
+                    .map(|n| from + n * v)
+                    .take_while(|&c| Board::in_bounds(c))
+                    .map(Board::index_for)
+                    .collect();
+                assert_eq!(BitIter::along(rays[d], d).collect::<Vec<_>>(), stepped, "Ray {} from {}", d, from);
+            }
+        }
+    }
+
+    #[test]
+    fn test_neighbors() {
+        for (i, &neighbors) in NEIGHBORS.iter().enumerate() {
+            let from: Vec2<Doubled> = Board::coords_for(i).into();
+            let mut expected: Vec<usize> = from.hex_neighbors().into_iter()
+                .filter(|&c| Board::in_bounds(c))
+                .map(Board::index_for)
+                .collect();
+            expected.sort();
+            assert_eq!(BitIter::ascending(neighbors).collect::<Vec<_>>(), expected, "Neighbors of {}", from);
+        }
+    }
+
+    #[test]
+    fn test_slide() {
+        let from = Board::index_for(Vec2::<Direct>::new(1, 0));
+        let blocker = Board::index_for(Vec2::<Direct>::new(4, 0));
+        // Direction 0 is LEFT, i.e. towards increasing x
+        assert_eq!(BitIter::along(slide(from, 0, !(1 << blocker)), 0).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(slide(from, 0, 0), 0);
+    }
+}
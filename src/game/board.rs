@@ -1,17 +1,34 @@
-use std::{ops::{Index, IndexMut}, fmt, str::FromStr};
+use std::{ops::Index, fmt, str::FromStr};
 
 use arrayvec::ArrayVec;
+use rand::Rng;
 
 use crate::util::{Element, Error, Result};
 
-use super::{Field, BOARD_FIELDS, Vec2, Direct, BOARD_SIZE, Move, Doubled, Team};
+use super::{Field, BOARD_FIELDS, Vec2, Direct, BOARD_SIZE, Move, Doubled, Team, TEAMS, MAX_FISH, MAX_HOLES, Bitboard, BitIter, DIRECTION_COUNT, NEIGHBORS, Region, TerritoryMap, slide};
 
 // Ported from https://github.com/software-challenge/backend/blob/a3145a91749abb73ca5ffd426fd2a77d9a90967a/plugin/src/main/kotlin/sc/plugin2023/Board.kt
 
+/// Every distinct field value, ordered by `Board::field_value_index`,
+/// so that indexing into the board can hand out references.
+static FIELD_VALUES: [Field; MAX_FISH + 1 + TEAMS] = [
+    Field::with_fish(0),
+    Field::with_fish(1),
+    Field::with_fish(2),
+    Field::with_fish(3),
+    Field::with_fish(4),
+    Field::with_penguin(Team::One),
+    Field::with_penguin(Team::Two),
+];
+
 /// The 8x8 game board, a two-dimensional grid of ice floes.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Internally stored as bitboards indexed by `Board::index_for`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Board {
-    fields: [Field; BOARD_FIELDS],
+    /// One plane per fish count, i.e. `fish[n - 1]` contains the floes with `n` fish.
+    fish: [Bitboard; MAX_FISH],
+    /// The fields occupied by each team's penguins.
+    penguins: [Bitboard; TEAMS],
 }
 
 impl Default for Board {
@@ -22,11 +39,48 @@ impl Default for Board {
 
 impl Board {
     /// The empty board.
-    pub const EMPTY: Self = Self { fields: [Field::EMPTY; BOARD_FIELDS] };
+    pub const EMPTY: Self = Self { fish: [0; MAX_FISH], penguins: [0; TEAMS] };
 
     /// Creates a new board with the given fields.
     pub const fn new(fields: [Field; BOARD_FIELDS]) -> Self {
-        Self { fields }
+        let mut board = Self::EMPTY;
+        let mut i = 0;
+        while i < BOARD_FIELDS {
+            let field = fields[i];
+            if let Some(team) = field.penguin() {
+                board.penguins[team.index()] |= 1 << i;
+            } else if field.fish() > 0 {
+                assert!(field.fish() <= MAX_FISH, "Too many fish on a field");
+                board.fish[field.fish() - 1] |= 1 << i;
+            }
+            i += 1;
+        }
+        board
+    }
+
+    /// Generates a random, point-symmetric board the way the server's
+    /// `generateFields` does: Each field of the first half is drawn from
+    /// the fish that are left for it, becoming one of at most `MAX_HOLES`
+    /// holes or a floe with more fish the more are left. The second half
+    /// is the point reflection of the first. Pass a seeded RNG to get
+    /// reproducible boards.
+    pub fn generate(rng: &mut impl Rng) -> Self {
+        let mut remaining_fish = BOARD_FIELDS;
+        let mut max_holes = MAX_HOLES;
+        let mut board = Self::EMPTY;
+        for i in 0..BOARD_FIELDS / 2 {
+            let rand = rng.gen_range(0..remaining_fish);
+            let fish = if rand < max_holes {
+                max_holes -= 1;
+                0
+            } else {
+                (rand - max_holes) / 20 + 1
+            };
+            remaining_fish -= fish;
+            board.set(Self::coords_for(i), Field::with_fish(fish));
+            board.set(Self::coords_for(BOARD_FIELDS - 1 - i), Field::with_fish(fish));
+        }
+        board
     }
 
     /// Checks whether the given coordinates are in bounds.
@@ -63,28 +117,110 @@ impl Board {
         }
     }
 
+    /// Replaces the field at the given position. Since the bitboards can't
+    /// lend out a field to write to, this takes the place of `IndexMut`.
+    /// Panics if the field has more than `MAX_FISH` fish.
+    pub fn set(&mut self, coords: impl Into<Vec2<Doubled>>, field: Field) {
+        let index = Self::index_for(coords);
+        let bit: Bitboard = 1 << index;
+        for plane in self.fish.iter_mut().chain(self.penguins.iter_mut()) {
+            *plane &= !bit;
+        }
+        if let Some(team) = field.penguin() {
+            self.penguins[team.index()] |= bit;
+        } else if field.fish() > 0 {
+            assert!(field.fish() <= MAX_FISH, "Too many fish on a field: {}", field.fish());
+            self.fish[field.fish() - 1] |= bit;
+        }
+    }
+
+    /// Replaces the fish at the given position by a penguin, returning the number of fish.
+    pub fn place(&mut self, coords: impl Into<Vec2<Doubled>> + Copy, team: Team) -> usize {
+        let fish = self[coords].fish();
+        self.set(coords, Field::with_penguin(team));
+        fish
+    }
+
     /// Fetches the possible moves from a given position.
     pub fn possible_moves_from<'a>(&'a self, coords: impl Into<Vec2<Doubled>>) -> impl Iterator<Item=Move> + 'a {
         let doubled: Vec2<Doubled> = coords.into();
-        Vec2::<Doubled>::DIRECTIONS
-            .into_iter()
-            .flat_map(move |v| (1..BOARD_SIZE as i32)
-                .map(move |n| Move::sliding(doubled, n * v))
-                .take_while(|c| self.get(c.to()).unwrap_or_default().fish() > 0))
+        let index = Self::index_for(doubled);
+        let floes = self.floes();
+        (0..DIRECTION_COUNT)
+            .flat_map(move |d| BitIter::along(slide(index, d, floes), d)
+                .map(move |i| Move::between(doubled, Self::coords_for(i))))
     }
 
     /// Fetches an iterator over the fields with coordinates.
     pub fn fields(&self) -> impl Iterator<Item=(Vec2<Doubled>, Field)> {
-        self.fields
-            .into_iter()
-            .enumerate()
-            .map(|(i, f)| (Self::coords_for(i).into(), f))
+        let board = *self;
+        (0..BOARD_FIELDS).map(move |i| (Self::coords_for(i).into(), FIELD_VALUES[board.field_value_index(i)]))
     }
 
     /// Fetches the penguins on the board.
     pub fn penguins(&self) -> impl Iterator<Item=(Vec2<Doubled>, Team)> {
-        self.fields()
-            .filter_map(|(c, f)| f.penguin().map(|p| (c, p)))
+        let [one, two] = self.penguins;
+        BitIter::ascending(one | two)
+            .map(move |i| (Self::coords_for(i).into(), if one & (1 << i) != 0 { Team::One } else { Team::Two }))
+    }
+
+    /// The floes with fish on them, i.e. the fields a penguin can move onto.
+    pub fn floes(&self) -> Bitboard {
+        self.fish.iter().fold(0, |acc, plane| acc | plane)
+    }
+
+    /// The floes with exactly the given number of fish.
+    pub fn fish_mask(&self, fish: usize) -> Bitboard {
+        match fish {
+            0 => !(self.floes() | self.penguins[0] | self.penguins[1]),
+            _ => self.fish.get(fish - 1).copied().unwrap_or(0),
+        }
+    }
+
+    /// Which team reaches each floe first and how many fish that is worth.
+    pub fn territory(&self) -> TerritoryMap {
+        TerritoryMap::new(self)
+    }
+
+    /// The connected components of the floes, using hex adjacency.
+    pub fn regions(&self) -> Vec<Region> {
+        Region::find_all(self)
+    }
+
+    /// The penguins without any floe next to them, which cannot move anymore.
+    pub fn isolated_penguins(&self) -> Bitboard {
+        let floes = self.floes();
+        BitIter::ascending(self.penguins[0] | self.penguins[1])
+            .filter(|&i| NEIGHBORS[i] & floes == 0)
+            .fold(0, |acc, i| acc | 1 << i)
+    }
+
+    /// The fields occupied by the given team's penguins.
+    pub fn penguin_mask(&self, team: Team) -> Bitboard {
+        self.penguins[team.index()]
+    }
+
+    /// The index of the field's value in `FIELD_VALUES`.
+    fn field_value_index(&self, index: usize) -> usize {
+        let bit: Bitboard = 1 << index;
+        if let Some(team) = (0..TEAMS).find(|&t| self.penguins[t] & bit != 0) {
+            MAX_FISH + 1 + team
+        } else {
+            (0..MAX_FISH).find(|&n| self.fish[n] & bit != 0).map_or(0, |n| n + 1)
+        }
+    }
+
+    /// Validates fields before they are converted into a board.
+    fn checked_fields(fields: impl IntoIterator<Item=Result<Field>>) -> Result<[Field; BOARD_FIELDS]> {
+        fields.into_iter()
+            .map(|f| f.and_then(|f| if f.fish() > MAX_FISH {
+                Err(Error::from(format!("Field has too many fish: {}", f.fish())))
+            } else {
+                Ok(f)
+            }))
+            .collect::<Result<ArrayVec<Field, BOARD_FIELDS>>>()?
+            .into_inner()
+            .map_err(|e| Error::from(format!("Board has wrong number of fields: {:?}", e)))
     }
 }
 
@@ -92,13 +228,7 @@ impl<V> Index<V> for Board where V: Copy + Into<Vec2<Doubled>> {
     type Output = Field;
 
     fn index(&self, index: V) -> &Field {
-        &self.fields[Self::index_for(index)]
-    }
-}
-
-impl<V> IndexMut<V> for Board where V: Copy + Into<Vec2<Doubled>> {
-    fn index_mut(&mut self, index: V) -> &mut Field {
-        &mut self.fields[Self::index_for(index)]
+        &FIELD_VALUES[self.field_value_index(Self::index_for(index))]
     }
 }
 
@@ -106,7 +236,7 @@ impl fmt::Display for Board {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for y in 0..BOARD_SIZE {
             for x in 0..BOARD_SIZE {
-                write!(f, "{}", self.fields[y * BOARD_SIZE + x])?;
+                write!(f, "{}", FIELD_VALUES[self.field_value_index(y * BOARD_SIZE + x)])?;
             }
             writeln!(f)?;
         }
@@ -118,14 +248,19 @@ impl FromStr for Board {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        Ok(Self {
-            fields: s.lines()
-                .filter(|l| !l.is_empty())
-                .flat_map(|l| l.chars().map(|c| c.try_into()))
-                .collect::<Result<ArrayVec<Field, BOARD_FIELDS>>>()?
-                .into_inner()
-                .map_err(|e| Error::from(format!("Board has wrong number of fields: {:?}", e)))?
-        })
+        Ok(Self::new(Self::checked_fields(s.lines()
+            .filter(|l| !l.is_empty())
+            .flat_map(|l| l.chars().map(|c| c.try_into())))?))
+    }
+}
+
+impl From<&Board> for Element {
+    fn from(board: &Board) -> Self {
+        Element::new("board")
+            .childs((0..BOARD_SIZE).map(|y| Element::new("list")
+                .childs((0..BOARD_SIZE).map(|x| Element::from(&FIELD_VALUES[board.field_value_index(y * BOARD_SIZE + x)])))
+                .build()))
+            .build()
     }
 }
 
@@ -133,13 +268,8 @@ impl TryFrom<&Element> for Board {
     type Error = Error;
 
     fn try_from(elem: &Element) -> Result<Self> {
-        Ok(Self {
-            fields: elem.childs_by_name("list")
-                .flat_map(|c| c.childs_by_name("field").map(|c| c.try_into()))
-                .collect::<Result<ArrayVec<Field, BOARD_FIELDS>>>()?
-                .into_inner()
-                .map_err(|e| Error::from(format!("Board has wrong number of fields: {:?}", e)))?
-        })
+        Ok(Self::new(Self::checked_fields(elem.childs_by_name("list")
+            .flat_map(|c| c.childs_by_name("field").map(|c| c.try_into())))?))
     }
 }
 
@@ -149,7 +279,9 @@ mod tests {
 
     use indoc::indoc;
 
-    use crate::{util::Element, game::{Board, Team, Vec2, Field, Direct}};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{util::Element, game::{Board, Team, Vec2, Field, Direct, BOARD_FIELDS, MAX_FISH, MAX_HOLES}};
 
     #[test]
     fn test_from_xml() {
@@ -251,9 +383,9 @@ mod tests {
     #[test]
     fn test_display_roundtrip() {
         let mut board = Board::EMPTY;
-        board[Vec2::<Direct>::new(2, 2)] = Field::with_fish(3);
-        board[Vec2::<Direct>::new(1, 0)] = Field::with_penguin(Team::One);
-        board[Vec2::<Direct>::new(1, 1)] = Field::with_penguin(Team::Two);
+        board.set(Vec2::<Direct>::new(2, 2), Field::with_fish(3));
+        board.set(Vec2::<Direct>::new(1, 0), Field::with_penguin(Team::One));
+        board.set(Vec2::<Direct>::new(1, 1), Field::with_penguin(Team::Two));
 
         assert_eq!(board.to_string(), indoc! {r#"
             0R000000
@@ -268,4 +400,34 @@ mod tests {
 
         assert_eq!(board.to_string().parse::<Board>().unwrap(), board);
     }
+
+    #[test]
+    fn test_generate() {
+        let board = Board::generate(&mut StdRng::seed_from_u64(1));
+        assert_eq!(board, Board::generate(&mut StdRng::seed_from_u64(1)));
+        assert_ne!(board, Board::generate(&mut StdRng::seed_from_u64(2)));
+
+        for seed in 0..1000 {
+            let board = Board::generate(&mut StdRng::seed_from_u64(seed));
+            let mut counts = [0; MAX_FISH + 1];
+            for i in 0..BOARD_FIELDS / 2 {
+                let fish = board[Board::coords_for(i)].fish();
+                assert_eq!(board[Board::coords_for(BOARD_FIELDS - 1 - i)].fish(), fish);
+                counts[fish] += 1;
+            }
+            // The bounds stated by the server: At most 5 holes, 22 2-fish
+            // and 21 3-fish floes per half.
+            assert!(counts[0] <= MAX_HOLES);
+            assert!(counts[2] <= 22);
+            assert!(counts[3] <= 21);
+            assert_eq!(board.penguins().count(), 0);
+        }
+    }
+
+    #[test]
+    fn test_to_xml() {
+        let mut board = Board::generate(&mut StdRng::seed_from_u64(3));
+        board.set(Vec2::<Direct>::new(4, 4), Field::with_penguin(Team::Two));
+        assert_eq!(Board::try_from(&Element::from(&board)).unwrap(), board);
+    }
 }
@@ -9,3 +9,12 @@ pub const TEAMS: usize = 2;
 
 /// Number of penguins per team.
 pub const PENGUINS_PER_TEAM: usize = 4;
+
+/// The maximum number of fish on a single floe.
+pub const MAX_FISH: usize = 4;
+
+/// The largest fish count a team can reach.
+pub const MAX_TEAM_FISH: usize = BOARD_FIELDS * MAX_FISH;
+
+/// The number of holes in each half of a generated board is at most this.
+pub const MAX_HOLES: usize = 5;
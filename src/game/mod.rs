@@ -1,15 +1,25 @@
+mod bitboard;
 mod board;
 mod constants;
 mod field;
 mod r#move;
+mod region;
+mod rule_violation;
 mod state;
 mod team;
+mod territory;
 mod vec2;
+mod zobrist;
 
+pub use bitboard::*;
 pub use board::*;
 pub use constants::*;
 pub use field::*;
 pub use r#move::*;
+pub use region::*;
+pub use rule_violation::*;
 pub use state::*;
 pub use team::*;
+pub use territory::*;
 pub use vec2::*;
+pub use zobrist::*;
@@ -0,0 +1,111 @@
+use super::{Bitboard, BitIter, Board, Doubled, Team, Vec2, MAX_FISH, NEIGHBORS, TEAMS};
+
+/// A connected component of floes, i.e. floes that can be reached
+/// from each other by stepping to neighboring floes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Region {
+    floes: Bitboard,
+    penguins: [Bitboard; TEAMS],
+    fish: usize,
+}
+
+impl Region {
+    /// Finds the connected components of the board's floes, ordered by their lowest field index.
+    pub fn find_all(board: &Board) -> Vec<Self> {
+        let floes = board.floes();
+        let penguins = [board.penguin_mask(Team::One), board.penguin_mask(Team::Two)];
+        let mut unvisited = floes;
+        let mut regions = Vec::new();
+        while unvisited != 0 {
+            let mut region = unvisited & unvisited.wrapping_neg();
+            let mut frontier = region;
+            while frontier != 0 {
+                frontier = BitIter::ascending(frontier).fold(0, |acc, i| acc | NEIGHBORS[i]) & floes & !region;
+                region |= frontier;
+            }
+            unvisited &= !region;
+            let around = BitIter::ascending(region).fold(0, |acc, i| acc | NEIGHBORS[i]);
+            regions.push(Self {
+                floes: region,
+                penguins: penguins.map(|p| p & around),
+                fish: (1..=MAX_FISH).map(|n| n * (board.fish_mask(n) & region).count_ones() as usize).sum(),
+            });
+        }
+        regions
+    }
+
+    /// The floes of the region.
+    pub fn floes(&self) -> Bitboard { self.floes }
+
+    /// The number of floes.
+    pub fn size(&self) -> usize { self.floes.count_ones() as usize }
+
+    /// The total number of fish on the floes.
+    pub fn fish(&self) -> usize { self.fish }
+
+    /// Whether the region contains the given field.
+    pub fn contains(&self, coords: impl Into<Vec2<Doubled>>) -> bool {
+        self.floes & (1 << Board::index_for(coords)) != 0
+    }
+
+    /// The fields of the team's penguins next to the region, which can move into it.
+    /// A penguin between several regions belongs to all of them.
+    pub fn penguin_mask(&self, team: Team) -> Bitboard { self.penguins[team.index()] }
+
+    /// The penguins next to the region.
+    pub fn penguins(&self) -> impl Iterator<Item=(Vec2<Doubled>, Team)> {
+        let [one, two] = self.penguins;
+        BitIter::ascending(one | two)
+            .map(move |i| (Board::coords_for(i).into(), if one & (1 << i) != 0 { Team::One } else { Team::Two }))
+    }
+
+    /// The only team with penguins next to the region, if there is exactly one.
+    pub fn owner(&self) -> Option<Team> {
+        match self.penguins {
+            [0, 0] => None,
+            [_, 0] => Some(Team::One),
+            [0, _] => Some(Team::Two),
+            _ => None,
+        }
+    }
+
+    /// Whether penguins of both teams can move into the region.
+    pub fn is_contested(&self) -> bool {
+        self.penguins.iter().all(|&p| p != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::game::{Board, Team, Vec2, Direct};
+
+    #[test]
+    fn test_regions() {
+        let board: Board = indoc! {"
+            R1300000
+            00000000
+            0000B200
+            00002R00
+            00000000
+            00000000
+            00004000
+            R0000000
+        "}.parse().unwrap();
+        let regions = board.regions();
+        assert_eq!(regions.len(), 3);
+
+        assert_eq!((regions[0].size(), regions[0].fish()), (2, 4));
+        assert_eq!(regions[0].owner(), Some(Team::One));
+        assert!(regions[0].contains(Vec2::<Direct>::new(2, 0)));
+
+        assert_eq!((regions[1].size(), regions[1].fish()), (2, 4));
+        assert!(regions[1].is_contested());
+        assert_eq!(regions[1].penguins().count(), 2);
+
+        assert_eq!((regions[2].size(), regions[2].fish()), (1, 4));
+        assert_eq!(regions[2].owner(), None);
+        assert_eq!(board.isolated_penguins(), board.penguin_mask(Team::One) & (1 << Board::index_for(Vec2::<Direct>::new(0, 7))));
+    }
+}
@@ -0,0 +1,34 @@
+use std::fmt;
+
+/// A reason why a move is illegal, see `State::validate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuleViolation {
+    /// The moved penguin does not belong to the current team (or there is none).
+    WrongTeam,
+    /// Penguins can only be placed on floes with a single fish.
+    NotSingleFish,
+    /// A team cannot place more penguins after all of them have been placed.
+    AllPenguinsPlaced,
+    /// Penguins cannot slide until all of the team's penguins have been placed.
+    PenguinsNotPlaced,
+    /// Penguins can only slide in straight lines.
+    NotStraight,
+    /// The path or the target is not free ice.
+    Blocked,
+    /// The move starts or ends off the board.
+    OutOfBounds,
+}
+
+impl fmt::Display for RuleViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongTeam => write!(f, "The moved penguin does not belong to the current team"),
+            Self::NotSingleFish => write!(f, "Penguins can only be placed on floes with a single fish"),
+            Self::AllPenguinsPlaced => write!(f, "All penguins have already been placed"),
+            Self::PenguinsNotPlaced => write!(f, "Penguins cannot slide until all of them have been placed"),
+            Self::NotStraight => write!(f, "Penguins can only slide in straight lines"),
+            Self::Blocked => write!(f, "The path is blocked"),
+            Self::OutOfBounds => write!(f, "The move leaves the board"),
+        }
+    }
+}
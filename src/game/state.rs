@@ -1,13 +1,22 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, hash::{Hash, Hasher}};
 
 use arrayvec::ArrayVec;
 
 use crate::util::{Element, Error, Result};
 
-use super::{Board, Move, Team, PENGUINS_PER_TEAM, TEAMS, Vec2, Field, Doubled, BOARD_FIELDS};
+use super::{Board, Move, Team, PENGUINS_PER_TEAM, TEAMS, MAX_TEAM_FISH, Vec2, Field, Doubled, BOARD_FIELDS, RuleViolation, board_key, field_key, fish_key, team_key};
 
 // Ported from https://github.com/software-challenge/backend/blob/a3145a91749abb73ca5ffd426fd2a77d9a90967a/plugin/src/main/kotlin/sc/plugin2023/GameState.kt
 
+/// The information needed to take back a move, see `State::make`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Undo {
+    /// The fish on the target field before the move.
+    fish: usize,
+    /// The most recent move before the move.
+    last_move: Option<Move>,
+}
+
 /// The state of the game at a point in time.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct State {
@@ -21,9 +30,18 @@ pub struct State {
     last_move: Option<Move>,
     /// The starting team.
     start_team: Team,
+    /// The Zobrist hash of board, fish and team to move, updated incrementally.
+    hash: u64,
 }
 
 impl State {
+    /// Creates a new state.
+    pub fn new(board: Board, turn: usize, fish: [usize; TEAMS], last_move: Option<Move>, start_team: Team) -> Self {
+        let mut state = Self { board, turn, fish, last_move, start_team, hash: 0 };
+        state.hash = state.compute_hash();
+        state
+    }
+
     /// Fetches the board.
     pub fn board(&self) -> &Board { &self.board }
 
@@ -39,7 +57,20 @@ impl State {
     /// Fetches the starting team.
     pub fn start_team(&self) -> Team { self.start_team }
 
+    /// Fetches the Zobrist hash. States that only differ in their most
+    /// recent move share the same hash, which makes transpositions detectable.
+    pub fn hash(&self) -> u64 { self.hash }
+
+    /// Computes the Zobrist hash from scratch.
+    fn compute_hash(&self) -> u64 {
+        board_key(&self.board)
+            ^ fish_key(Team::One, self.fish[0])
+            ^ fish_key(Team::Two, self.fish[1])
+            ^ team_key(self.current_team_from_turn())
+    }
+
     /// The current team, computed from the starting team and the turn.
+    #[allow(clippy::manual_is_multiple_of)]
     pub fn current_team_from_turn(&self) -> Team {
         self.start_team.opponent_if(|_| self.turn % 2 != 0)
     }
@@ -110,24 +141,126 @@ impl State {
         }
     }
 
+    /// Checks whether the given move is legal for the current team.
+    pub fn validate(&self, m: Move) -> std::result::Result<(), RuleViolation> {
+        let to = m.to();
+        if !Board::in_bounds(to) {
+            return Err(RuleViolation::OutOfBounds);
+        }
+        let Some(from) = m.from() else {
+            if self.penguins_placed() {
+                return Err(RuleViolation::AllPenguinsPlaced);
+            }
+            if self.board[to].fish() != 1 {
+                return Err(RuleViolation::NotSingleFish);
+            }
+            return Ok(());
+        };
+        if !Board::in_bounds(from) {
+            return Err(RuleViolation::OutOfBounds);
+        }
+        if self.board[from].penguin() != Some(self.current_team()) {
+            return Err(RuleViolation::WrongTeam);
+        }
+        if !self.penguins_placed() {
+            return Err(RuleViolation::PenguinsNotPlaced);
+        }
+        let delta = to - from;
+        if delta == Vec2::ZERO || !delta.straight() {
+            return Err(RuleViolation::NotStraight);
+        }
+        let step = if delta.y == 0 {
+            Vec2::new(2 * delta.x.signum(), 0)
+        } else {
+            Vec2::new(delta.x.signum(), delta.y.signum())
+        };
+        let mut pos = from;
+        while pos != to {
+            pos = pos + step;
+            if self.board[pos].fish() == 0 {
+                return Err(RuleViolation::Blocked);
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs the given move if it is legal, otherwise leaves the state untouched.
+    pub fn try_perform(&mut self, m: Move) -> std::result::Result<(), RuleViolation> {
+        self.validate(m)?;
+        self.perform(m);
+        Ok(())
+    }
+
     /// Performs the given move.
     pub fn perform(&mut self, m: Move) {
         let to = m.to();
         let team = self.current_team();
+        let penguin = Field::with_penguin(team);
         if let Some(from) = m.from() {
             // Prepare penguin slide
             debug_assert!(self.board[from].penguin() == Some(team), "Wrong color");
             debug_assert!(self.current_pieces().count() >= PENGUINS_PER_TEAM, "Cannot slide until all penguins have been placed");
             debug_assert!((to - from).straight(), "Can only move in straight lines");
-            self.board[from] = Field::EMPTY;
+            self.board.set(from, Field::EMPTY);
+            self.hash ^= field_key(Board::index_for(from), penguin);
         } else {
             // Prepare penguin placement
             debug_assert!(self.current_pieces().count() < PENGUINS_PER_TEAM, "Cannot place after all penguins have been placed");
             debug_assert!(self.board[to].fish() == 1, "Cannot place on more than one fish");
         }
-        self.fish[team.index()] += self.board[to].place(team);
+        self.hash ^= field_key(Board::index_for(to), self.board[to]) ^ field_key(Board::index_for(to), penguin);
+        let fish = self.board.place(to, team);
+        self.update_fish(team, fish as isize);
+        self.hash ^= team_key(self.current_team_from_turn());
         self.last_move = Some(m);
         self.turn += 1;
+        self.hash ^= team_key(self.current_team_from_turn());
+        debug_assert_eq!(self.hash, self.compute_hash(), "Hash out of sync after {}", m);
+    }
+
+    /// Adds the given (possibly negative) amount to the team's fish, updating the hash.
+    fn update_fish(&mut self, team: Team, delta: isize) {
+        let fish = &mut self.fish[team.index()];
+        self.hash ^= fish_key(team, *fish);
+        *fish = fish.checked_add_signed(delta).expect("Fish out of range");
+        self.hash ^= fish_key(team, *fish);
+    }
+
+    /// Performs the given move, returning what is needed to take it back with `State::unmake`.
+    pub fn make(&mut self, m: Move) -> Undo {
+        let undo = Undo {
+            fish: self.board[m.to()].fish(),
+            last_move: self.last_move,
+        };
+        #[cfg(debug_assertions)]
+        let before = *self;
+        self.perform(m);
+        #[cfg(debug_assertions)]
+        {
+            let mut restored = *self;
+            restored.unmake(m, undo);
+            debug_assert_eq!(restored, before, "Unmaking {} does not restore the state", m);
+        }
+        undo
+    }
+
+    /// Takes back the given move, which has to be the most recent one made with `State::make`.
+    pub fn unmake(&mut self, m: Move, undo: Undo) {
+        let to = m.to();
+        let team = self.board[to].penguin().expect("No penguin on the target field of the move to unmake");
+        let penguin = Field::with_penguin(team);
+        let restored = Field::with_fish(undo.fish);
+        self.board.set(to, restored);
+        self.hash ^= field_key(Board::index_for(to), penguin) ^ field_key(Board::index_for(to), restored);
+        if let Some(from) = m.from() {
+            self.board.set(from, penguin);
+            self.hash ^= field_key(Board::index_for(from), penguin);
+        }
+        self.update_fish(team, -(undo.fish as isize));
+        self.hash ^= team_key(self.current_team_from_turn());
+        self.last_move = undo.last_move;
+        self.turn -= 1;
+        self.hash ^= team_key(self.current_team_from_turn());
     }
 
     /// Fetches the state after the given move.
@@ -137,13 +270,23 @@ impl State {
         next
     }
 
-    /// Returns whether a winner can already be determined by the current state
+    /// Returns whether a winner can already be determined by the current state,
+    /// i.e. whether a team is ahead by more fish than the other can still reach
     pub fn is_terminal(&self) -> bool {
         if self.is_over() {
             return true
         }
-        let total_fishes: usize = self.board().fields().fold(0, |c, f| c + f.1.fish()) + self.fish.iter().sum::<usize>();
-        self.fish.iter().any(|&f| (f as f64 / total_fishes as f64) > 0.5)
+        let regions = self.board.regions();
+        let placed = (self.board.penguin_mask(Team::One) | self.board.penguin_mask(Team::Two)).count_ones() as usize == PENGUINS_PER_TEAM * TEAMS;
+        let reachable = |team: Team| -> usize {
+            regions.iter()
+                // Penguins that are yet to be placed could reach any region
+                .filter(|r| !placed || r.penguin_mask(team) != 0)
+                .map(|r| r.fish())
+                .sum()
+        };
+        let [one, two] = self.fish;
+        one > two + reachable(Team::Two) || two > one + reachable(Team::One)
     }
 
 }
@@ -152,17 +295,42 @@ impl TryFrom<&Element> for State {
     type Error = Error;
 
     fn try_from(elem: &Element) -> Result<Self> {
-        Ok(State {
-            board: elem.child_by_name("board")?.try_into()?,
-            turn: elem.attribute("turn")?.parse()?,
-            fish: elem.child_by_name("fishes")?
-                .childs_by_name("int").map(|c| Ok(c.content().parse()?))
+        Ok(State::new(
+            elem.child_by_name("board")?.try_into()?,
+            elem.attribute("turn")?.parse()?,
+            elem.child_by_name("fishes")?
+                .childs_by_name("int").map(|c| match c.content().parse()? {
+                    fish if fish > MAX_TEAM_FISH => Err(Error::from(format!("Team has too many fish: {}", fish))),
+                    fish => Ok(fish),
+                })
                 .collect::<Result<ArrayVec<usize, TEAMS>>>()?
                 .into_inner()
                 .map_err(|e| Error::from(format!("State has wrong number of fish teams: {:?}", e)))?,
-            last_move: elem.child_by_name("lastMove").ok().and_then(|m| m.try_into().ok()),
-            start_team: elem.child_by_name("startTeam")?.content().parse()?,
-        })
+            elem.child_by_name("lastMove").ok().and_then(|m| m.try_into().ok()),
+            elem.child_by_name("startTeam")?.content().parse()?,
+        ))
+    }
+}
+
+impl From<&State> for Element {
+    fn from(state: &State) -> Self {
+        Element::new("state")
+            .attribute("class", "state")
+            .attribute("turn", state.turn)
+            .child(Element::new("startTeam").content(&state.start_team.to_string()))
+            .child(&state.board)
+            .option_child(state.last_move.map(|m| Element::new("lastMove")
+                .option_child(m.from().map(|v| Element::new("from").attribute("x", v.x).attribute("y", v.y)))
+                .child(Element::new("to").attribute("x", m.to().x).attribute("y", m.to().y))))
+            .child(Element::new("fishes")
+                .childs(state.fish.iter().map(|f| Element::new("int").content(&f.to_string()).build())))
+            .build()
+    }
+}
+
+impl Hash for State {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
     }
 }
 
@@ -172,7 +340,9 @@ mod tests {
 
     use indoc::indoc;
 
-    use crate::{util::Element, game::{Board, Team, State, Move, Vec2, Doubled}};
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use crate::{util::Element, game::{Board, Team, State, Move, Vec2, Doubled, RuleViolation}};
 
     #[test]
     fn test_from_xml() {
@@ -269,13 +439,13 @@ mod tests {
                     <int>0</int>
                 </fishes>
             </state>
-        "#}).unwrap()).unwrap(), State {
-            board: Board::EMPTY,
-            turn: 1,
-            fish: [1, 0],
-            last_move: Some(Move::placing(Vec2::<Doubled>::new(13, 5))),
-            start_team: Team::One,
-        });
+        "#}).unwrap()).unwrap(), State::new(
+            Board::EMPTY,
+            1,
+            [1, 0],
+            Some(Move::placing(Vec2::<Doubled>::new(13, 5))),
+            Team::One,
+        ));
     }
 
     #[test]
@@ -290,13 +460,13 @@ mod tests {
             001000B0
             1R0100B0
         "#}.parse::<Board>().unwrap();
-        let state = State {
+        let state = State::new(
             board,
-            turn: 57,
-            fish: [10, 20], // Irrelevant
-            last_move: None, // Irrelevant
-            start_team: Team::One,
-        };
+            57,
+            [10, 20], // Irrelevant
+            None, // Irrelevant
+            Team::One,
+        );
         assert_eq!(state.possible_moves(), vec![
             Move::between(Vec2::<Doubled>::new(8, 4), Vec2::<Doubled>::new(10, 4)),
             Move::between(Vec2::<Doubled>::new(8, 4), Vec2::<Doubled>::new(7, 5)),
@@ -304,4 +474,132 @@ mod tests {
             Move::between(Vec2::<Doubled>::new(3, 7), Vec2::<Doubled>::new(1, 7)),
         ]);
     }
+
+    #[test]
+    fn test_make_unmake() {
+        let board = indoc! {r#"
+            11213121
+            21131412
+            31111213
+            12141111
+            11114121
+            31211113
+            21412311
+            12131211
+        "#}.parse::<Board>().unwrap();
+        let mut state = State::new(board, 0, [0, 0], None, Team::One);
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut history = Vec::new();
+        while let Some(&m) = state.possible_moves().choose(&mut rng) {
+            let before = state;
+            let undo = state.make(m);
+            history.push((before, m, undo));
+        }
+        assert!(state.turn() > 8);
+        while let Some((before, m, undo)) = history.pop() {
+            state.unmake(m, undo);
+            assert_eq!(state, before);
+        }
+    }
+
+    #[test]
+    fn test_hash_transposition() {
+        let board = indoc! {r#"
+            11213121
+            21131412
+            31111213
+            12141111
+            11114121
+            31211113
+            21412311
+            12131211
+        "#}.parse::<Board>().unwrap();
+        let start = State::new(board, 0, [0, 0], None, Team::One);
+        let a = Move::placing(Vec2::<Doubled>::new(0, 0));
+        let b = Move::placing(Vec2::<Doubled>::new(2, 0));
+        let c = Move::placing(Vec2::<Doubled>::new(6, 0));
+
+        let first = start.child(a).child(b).child(c);
+        let second = start.child(c).child(b).child(a);
+        assert_eq!(first.board(), second.board());
+        assert_eq!(first.hash(), second.hash());
+        assert_ne!(first.hash(), start.child(b).child(a).child(c).hash());
+        assert_eq!(first.hash(), State::new(*first.board(), 3, [2, 1], None, Team::One).hash());
+    }
+
+    #[test]
+    fn test_validate() {
+        let board = indoc! {r#"
+            R1B12100
+            R0B13000
+            R2B11100
+            11B12102
+            11111111
+            11111111
+            11111111
+            11111111
+        "#}.parse::<Board>().unwrap();
+        let placing = State::new(board, 0, [0, 0], None, Team::One);
+        assert_eq!(placing.validate(Move::placing(Vec2::<Doubled>::new(0, 4))), Ok(()));
+        assert_eq!(placing.validate(Move::placing(Vec2::<Doubled>::new(9, 1))), Err(RuleViolation::NotSingleFish));
+        assert_eq!(placing.validate(Move::placing(Vec2::<Doubled>::new(0, 0))), Err(RuleViolation::NotSingleFish));
+        assert_eq!(placing.validate(Move::placing(Vec2::<Doubled>::new(16, 0))), Err(RuleViolation::OutOfBounds));
+        assert_eq!(placing.validate(Move::between(Vec2::<Doubled>::new(0, 0), Vec2::<Doubled>::new(2, 0))), Err(RuleViolation::PenguinsNotPlaced));
+
+        let mut board = board;
+        board.set(Vec2::<Doubled>::new(1, 3), Team::One.into());
+        let mut sliding = State::new(board, 0, [0, 0], None, Team::One);
+        assert_eq!(sliding.validate(Move::placing(Vec2::<Doubled>::new(0, 4))), Err(RuleViolation::AllPenguinsPlaced));
+        assert_eq!(sliding.validate(Move::between(Vec2::<Doubled>::new(0, 0), Vec2::<Doubled>::new(1, 1))), Err(RuleViolation::Blocked));
+        assert_eq!(sliding.validate(Move::between(Vec2::<Doubled>::new(0, 0), Vec2::<Doubled>::new(6, 0))), Err(RuleViolation::Blocked));
+        assert_eq!(sliding.validate(Move::between(Vec2::<Doubled>::new(0, 0), Vec2::<Doubled>::new(3, 1))), Err(RuleViolation::NotStraight));
+        assert_eq!(sliding.validate(Move::between(Vec2::<Doubled>::new(0, 0), Vec2::<Doubled>::new(0, 0))), Err(RuleViolation::NotStraight));
+        assert_eq!(sliding.validate(Move::between(Vec2::<Doubled>::new(4, 0), Vec2::<Doubled>::new(6, 0))), Err(RuleViolation::WrongTeam));
+        assert_eq!(sliding.validate(Move::between(Vec2::<Doubled>::new(0, 0), Vec2::<Doubled>::new(-1, -1))), Err(RuleViolation::OutOfBounds));
+
+        assert_eq!(sliding.try_perform(Move::between(Vec2::<Doubled>::new(0, 0), Vec2::<Doubled>::new(-2, 0))), Err(RuleViolation::OutOfBounds));
+        assert_eq!(sliding.turn(), 0);
+        assert_eq!(sliding.try_perform(Move::between(Vec2::<Doubled>::new(0, 0), Vec2::<Doubled>::new(2, 0))), Ok(()));
+        assert_eq!(sliding.fish(Team::One), 1);
+    }
+
+    #[test]
+    fn test_is_terminal() {
+        // One can still reach 4 + 4 fish, Two only 3
+        let board = indoc! {r#"
+            R4000000
+            R0000000
+            R0000000
+            R4000000
+            00000000
+            000000B3
+            00000000
+            00000BBB
+        "#}.parse::<Board>().unwrap();
+        assert!(!State::new(board, 8, [10, 17], None, Team::One).is_terminal());
+        assert!(State::new(board, 8, [10, 19], None, Team::One).is_terminal());
+        assert!(State::new(board, 8, [14, 10], None, Team::One).is_terminal());
+        assert!(!State::new(board, 8, [14, 12], None, Team::One).is_terminal());
+    }
+
+    #[test]
+    fn test_to_xml() {
+        let board = indoc! {r#"
+            00000000
+            0000000R
+            00000B00
+            0B000000
+            10R0R102
+            00010000
+            001000B0
+            1R0100B0
+        "#}.parse::<Board>().unwrap();
+        let state = State::new(board, 57, [10, 20], None, Team::One)
+            .child(Move::between(Vec2::<Doubled>::new(8, 4), Vec2::<Doubled>::new(10, 4)));
+        assert_eq!(State::try_from(&Element::from(&state)).unwrap(), state);
+
+        // Fish counts out of range are rejected instead of breaking the hash
+        let xml = Element::from(&state).to_string().replace("<int>20</int>", "<int>1000</int>");
+        assert!(State::try_from(&Element::from_str(&xml).unwrap()).is_err());
+    }
 }
@@ -1,7 +1,7 @@
 use std::str::FromStr;
 use std::fmt;
 
-use crate::util::{Error, Result};
+use crate::util::{Element, Error, Result};
 
 /// A playing party in the game.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -21,7 +21,7 @@ impl Team {
     }
 
     /// The team's index.
-    pub fn index(self) -> usize {
+    pub const fn index(self) -> usize {
         match self {
             Self::One => 0,
             Self::Two => 1,
@@ -82,3 +82,32 @@ impl FromStr for Team {
         }
     }
 }
+
+impl From<&Team> for Element {
+    fn from(team: &Team) -> Self {
+        Element::new("team").content(&team.to_string()).build()
+    }
+}
+
+impl TryFrom<&Element> for Team {
+    type Error = Error;
+
+    fn try_from(elem: &Element) -> Result<Self> {
+        elem.content().parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{util::Element, game::Team};
+
+    #[test]
+    fn test_xml_roundtrip() {
+        assert_eq!(Element::from(&Team::Two), Element::from_str("<team>TWO</team>").unwrap());
+        for team in [Team::One, Team::Two] {
+            assert_eq!(Team::try_from(&Element::from(&team)).unwrap(), team);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::fmt;
+
+use super::{Bitboard, BitIter, Board, Doubled, Team, Vec2, BOARD_FIELDS, BOARD_SIZE, DIRECTION_COUNT, MAX_FISH, TEAMS, slide};
+
+/// Which team reaches a field first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ownership {
+    /// The team reaches the field in fewer moves.
+    Owned(Team),
+    /// Both teams reach the field in the same number of moves.
+    Contested,
+    /// No team can reach the field.
+    Unreachable,
+}
+
+/// For every floe, how many moves each team needs at least to get there
+/// on the current board, i.e. without taking floes away on the way and
+/// with all penguins blocking the fields they stand on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerritoryMap {
+    /// The number of moves per team and field index, `u8::MAX` if unreachable.
+    distances: [[u8; BOARD_FIELDS]; TEAMS],
+    /// The floes reached first per team.
+    owned: [Bitboard; TEAMS],
+    /// The fish on the floes reached first per team.
+    fish: [usize; TEAMS],
+}
+
+impl TerritoryMap {
+    /// Computes the territory of both teams with a breadth-first search per team.
+    pub fn new(board: &Board) -> Self {
+        let floes = board.floes();
+        let mut distances = [[u8::MAX; BOARD_FIELDS]; TEAMS];
+        let mut reached = [0; TEAMS];
+        for team in [Team::One, Team::Two] {
+            let mut frontier = board.penguin_mask(team);
+            let mut distance = 1;
+            while frontier != 0 {
+                let next = BitIter::ascending(frontier)
+                    .fold(0, |acc, i| (0..DIRECTION_COUNT).fold(acc, |acc, d| acc | slide(i, d, floes)))
+                    & !reached[team.index()];
+                for i in BitIter::ascending(next) {
+                    distances[team.index()][i] = distance;
+                }
+                reached[team.index()] |= next;
+                frontier = next;
+                distance += 1;
+            }
+        }
+
+        let [one, two] = distances;
+        let mut owned = [0; TEAMS];
+        for i in BitIter::ascending(reached[0] | reached[1]) {
+            if one[i] < two[i] {
+                owned[0] |= 1 << i;
+            } else if two[i] < one[i] {
+                owned[1] |= 1 << i;
+            }
+        }
+        let fish = owned.map(|fields| (1..=MAX_FISH).map(|n| n * (board.fish_mask(n) & fields).count_ones() as usize).sum());
+        Self { distances, owned, fish }
+    }
+
+    /// Which team reaches the field first.
+    pub fn owner(&self, coords: impl Into<Vec2<Doubled>>) -> Ownership {
+        let index = Board::index_for(coords);
+        let [one, two] = [self.distances[0][index], self.distances[1][index]];
+        if one == u8::MAX && two == u8::MAX {
+            Ownership::Unreachable
+        } else if one < two {
+            Ownership::Owned(Team::One)
+        } else if two < one {
+            Ownership::Owned(Team::Two)
+        } else {
+            Ownership::Contested
+        }
+    }
+
+    /// The number of moves the team needs at least to reach the field, if it can.
+    pub fn distance(&self, team: Team, coords: impl Into<Vec2<Doubled>>) -> Option<usize> {
+        let distance = self.distances[team.index()][Board::index_for(coords)];
+        (distance != u8::MAX).then_some(distance as usize)
+    }
+
+    /// The floes the team reaches first.
+    pub fn owned(&self, team: Team) -> Bitboard {
+        self.owned[team.index()]
+    }
+
+    /// The fish on the floes the team reaches first.
+    pub fn fish(&self, team: Team) -> usize {
+        self.fish[team.index()]
+    }
+}
+
+impl fmt::Display for TerritoryMap {
+    /// Draws the owner of every field, dashes for contested
+    /// fields and dots for unreachable ones, in the layout of `Board`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for y in 0..BOARD_SIZE {
+            for x in 0..BOARD_SIZE {
+                let c = match self.owner(Board::coords_for(y * BOARD_SIZE + x)) {
+                    Ownership::Owned(team) => team.letter(),
+                    Ownership::Contested => '-',
+                    Ownership::Unreachable => '.',
+                };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::game::{Board, Team, Vec2, Direct};
+
+    use super::Ownership;
+
+    #[test]
+    fn test_territory() {
+        let board: Board = indoc! {"
+            R1000000
+            00200000
+            00030000
+            00000000
+            00004000
+            00000000
+            00000010
+            000000B2
+        "}.parse().unwrap();
+        let territory = board.territory();
+
+        assert_eq!(territory.owner(Vec2::<Direct>::new(1, 0)), Ownership::Owned(Team::One));
+        assert_eq!(territory.distance(Team::One, Vec2::<Direct>::new(1, 0)), Some(1));
+        assert_eq!(territory.distance(Team::Two, Vec2::<Direct>::new(1, 0)), None);
+        assert_eq!(territory.owner(Vec2::<Direct>::new(6, 6)), Ownership::Owned(Team::Two));
+        assert_eq!(territory.owner(Vec2::<Direct>::new(0, 3)), Ownership::Unreachable);
+        assert_eq!(territory.fish(Team::One), 1);
+        assert_eq!(territory.fish(Team::Two), 1 + 2);
+        assert_eq!(territory.to_string().lines().next(), Some(".R......"));
+    }
+}
@@ -0,0 +1,86 @@
+use super::{Board, Field, Team, BOARD_FIELDS, MAX_FISH, MAX_TEAM_FISH, TEAMS};
+
+/// The number of distinct non-empty field values, i.e. every fish count and every team's penguin.
+const FIELD_KINDS: usize = MAX_FISH + TEAMS;
+
+/// The random keys, generated at compile time so that hashes are stable across runs.
+struct Keys {
+    fields: [[u64; FIELD_KINDS]; BOARD_FIELDS],
+    fish: [[u64; MAX_TEAM_FISH + 1]; TEAMS],
+    second_team: u64,
+}
+
+static KEYS: Keys = keys();
+
+/// A step of the SplitMix64 generator.
+const fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+const fn keys() -> Keys {
+    let mut state = 2023;
+    let mut keys = Keys {
+        fields: [[0; FIELD_KINDS]; BOARD_FIELDS],
+        fish: [[0; MAX_TEAM_FISH + 1]; TEAMS],
+        second_team: 0,
+    };
+    let mut i = 0;
+    while i < BOARD_FIELDS {
+        let mut k = 0;
+        while k < FIELD_KINDS {
+            keys.fields[i][k] = split_mix(&mut state);
+            k += 1;
+        }
+        i += 1;
+    }
+    let mut t = 0;
+    while t < TEAMS {
+        // Zero fish hash to zero, so that fresh states only depend on the board
+        let mut f = 1;
+        while f <= MAX_TEAM_FISH {
+            keys.fish[t][f] = split_mix(&mut state);
+            f += 1;
+        }
+        t += 1;
+    }
+    keys.second_team = split_mix(&mut state);
+    keys
+}
+
+/// The key of a field at the given index. Empty fields hash to zero.
+#[inline]
+pub fn field_key(index: usize, field: Field) -> u64 {
+    if let Some(team) = field.penguin() {
+        KEYS.fields[index][MAX_FISH + team.index()]
+    } else if field.fish() > 0 {
+        KEYS.fields[index][field.fish() - 1]
+    } else {
+        0
+    }
+}
+
+/// The key of a team's fish count.
+#[inline]
+pub fn fish_key(team: Team, fish: usize) -> u64 {
+    KEYS.fish[team.index()][fish]
+}
+
+/// The key of the team whose turn it is, ignoring whether it can move.
+#[inline]
+pub fn team_key(team: Team) -> u64 {
+    match team {
+        Team::One => 0,
+        Team::Two => KEYS.second_team,
+    }
+}
+
+/// Computes the hash of a board from scratch.
+pub fn board_key(board: &Board) -> u64 {
+    board.fields()
+        .map(|(c, f)| field_key(Board::index_for(c), f))
+        .fold(0, |acc, k| acc ^ k)
+}
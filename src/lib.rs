@@ -1,4 +1,17 @@
+pub mod alpha_beta;
+#[cfg(feature = "async")]
+pub mod async_client;
 pub mod client;
+pub mod config;
+pub mod evaluator;
 pub mod protocol;
+pub mod replay;
+pub mod server;
 pub mod game;
+pub mod logic;
+pub mod observer;
+pub mod search;
+pub mod selfplay;
+#[cfg(test)]
+mod test_util;
 pub mod util;
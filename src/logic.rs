@@ -8,60 +8,222 @@ Special thanks to the our computer sience teacher Mr. Ries
 */
 
 use log::{info, debug};
-use std::{time, collections::HashSet};
+use std::{time, thread, sync::atomic::{AtomicUsize, Ordering}};
 
-use socha_client_2023::{client::GameClientDelegate, game::{Move, Team, State, Vec2, Doubled}};
+use crate::{client::{BestMove, GameClientDelegate}, evaluator::{Evaluator, Territory}, game::{Move, Team, State}, protocol::GameResult, search::{EndgameSolver, SearchStats, TimeManager}};
 
 pub struct OwnLogic {
     pub game_tree: Option<Node>,
+    /// Plans the time to search per move.
+    pub time_manager: TimeManager,
+    /// The weight of exploration in the UCB1 formula.
+    pub exploration_constant: f64,
+    /// The number of threads to search with.
+    pub threads: usize,
+    /// Estimates the outcome of the game at the leaves of the tree.
+    pub evaluator: Box<dyn Evaluator>,
+    /// The number of playouts of the most recent search.
+    pub playouts: u64,
+    /// When the pending move request was received.
+    pub move_requested: Option<time::Instant>,
+    /// Solves small endgames exactly instead of searching them.
+    pub solver: EndgameSolver,
+    /// Seeds the order of the children, which breaks ties between equally good
+    /// moves, or `None` to keep the order the moves are generated in.
+    pub seed: Option<u64>,
+    /// The memory the game tree may use in bytes, after which it stops growing.
+    pub tree_memory: usize,
+    /// The number of nodes in the game tree.
+    pub tree_nodes: usize,
+    /// The statistics of the most recent move request.
+    pub last_search: Option<SearchStats>,
+    /// The best move of the running search, updated between its rounds.
+    pub best_move: BestMove,
 }
 
-pub const TIME_LIMIT: u128 = 1800;
+/// The time to search per call to `ponder` in milliseconds.
+pub const PONDER_SLICE: u64 = 20;
+/// The time to search between checks whether to stop early in milliseconds.
+pub const CHECK_INTERVAL: u64 = 50;
 pub const EXPLORATION_CONSTANT: f64 = 2.82;
+/// The default memory limit of the game tree in bytes.
+pub const TREE_MEMORY: usize = 1 << 30;
+/// Spreads seeds and hashes over all bits, the golden ratio scaled to 64 bits.
+const SEED_STEP: u64 = 0x9E37_79B9_7F4A_7C15;
+
+impl Default for OwnLogic {
+    fn default() -> Self {
+        Self {
+            game_tree: None,
+            time_manager: TimeManager::default(),
+            exploration_constant: EXPLORATION_CONSTANT,
+            threads: 1,
+            evaluator: Box::new(Territory),
+            playouts: 0,
+            move_requested: None,
+            solver: EndgameSolver::default(),
+            seed: None,
+            tree_memory: TREE_MEMORY,
+            tree_nodes: 0,
+            last_search: None,
+            best_move: BestMove::default(),
+        }
+    }
+}
 
 impl GameClientDelegate for OwnLogic {
     fn request_move(&mut self, state: &State, _my_team: Team) -> Move {
 
         info!("Requested move");
 
-        let start = time::Instant::now();
+        let start = self.move_requested.take().unwrap_or_else(time::Instant::now);
+        let deadline = self.time_manager.deadline(start, state);
+        let budget = deadline - start;
+        let team = state.current_team();
 
-        // Check if the game tree contains the current state
-        let mut alpha_root = None;
-        if self.game_tree.is_some() {
-            let game_tree = self.game_tree.take().unwrap();
-            for node in game_tree.children.iter().flat_map(|n| n.children.iter().chain(Some(n))) {
-                if node.state == *state {
-                    alpha_root = Some(node.to_owned());
-                    break;
+        // Solve small endgames exactly, falling back to MCTS if that takes too long
+        if EndgameSolver::is_applicable(state) && !budget.is_zero() {
+            if let Some(solution) = self.solver.solve(state, team, start + budget / 2) {
+                info!("Solved endgame with {} nodes in {} ms, fish difference {}", self.solver.nodes(), start.elapsed().as_millis(), solution.value);
+                if let Some(best_move) = solution.best_move {
+                    self.last_search = Some(SearchStats { time: start.elapsed(), nodes: self.solver.nodes(), depth: None, value: Some(solution.value as f64) });
+                    self.game_tree = None;
+                    return best_move;
                 }
             }
         }
-        // Else create a new game tree
-        let mut alpha_root: Node = alpha_root.unwrap_or(Node::new(state.clone()));
 
+        let mut alpha_root = self.take_subtree(state);
         let root = &mut alpha_root;
-        if root.children.is_empty() {
-            root.expand();
-        }
-        
-        // Run MCTS algorithm for a given amount of time
-        while start.elapsed().as_millis() < TIME_LIMIT && !root.fully_expanded {
-            root.mcts(&state.current_team());
+        self.expand_root(root, team);
+
+        // Run MCTS algorithm until the planned time is used or the best move is clearly ahead
+        self.playouts = 0;
+        while time::Instant::now() < deadline && !root.fully_expanded {
+            let round = deadline.min(time::Instant::now() + time::Duration::from_millis(CHECK_INTERVAL));
+            self.playouts += self.search(root, &team, round);
+            self.best_move.set(root.best_child().state.last_move().unwrap());
+            if self.time_manager.can_stop(start.elapsed(), budget, self.playouts, root.lead()) {
+                info!("Stopping early, the best move is clearly ahead");
+                break;
+            }
         }
+        info!("Searched {} playouts in {} ms (budget {} ms), tree of {} nodes", self.playouts, start.elapsed().as_millis(), budget.as_millis(), self.tree_nodes);
 
         // Select move with highest reward
-        let best_move = root.children.iter().max_by_key(|c| ((c.total/c.visits as f64)*1000000.) as i32).unwrap().state.last_move().unwrap().clone();
+        let best = root.best_child();
+        let best_move = best.state.last_move().unwrap();
+        self.last_search = Some(SearchStats { time: start.elapsed(), nodes: self.playouts, depth: None, value: Some(best.total / best.visits.max(1) as f64) });
         // Save the game tree for the next move
         self.game_tree = Some(alpha_root);
         best_move
 
     }
 
-    fn on_game_end(&mut self, _result: &socha_client_2023::protocol::GameResult) {}
+    fn on_game_end(&mut self, _result: &GameResult) {
+        self.game_tree = None;
+        self.tree_nodes = 0;
+        self.solver.clear();
+    }
+
+    fn on_update_state(&mut self, state: &State) { debug!("Board:\n{}\nTerritory:\n{}", state.board(), state.board().territory()) }
+
+    fn on_move_request(&mut self, received: time::Instant) { self.move_requested = Some(received); }
+
+    fn search_stats(&self) -> Option<SearchStats> { self.last_search }
+
+    fn best_move(&self) -> Option<BestMove> { Some(self.best_move.clone()) }
+
+    fn ponder(&mut self, state: &State, my_team: Team) -> bool {
+        let mut root = self.take_subtree(state);
+
+        // Search for a short slice only, the client calls again until the opponent has moved
+        let deadline = time::Instant::now() + time::Duration::from_millis(PONDER_SLICE);
+        self.search(&mut root, &my_team, deadline);
+        let searching = !root.fully_expanded;
+        self.game_tree = Some(root);
+        searching
+    }
+
+}
+
+impl OwnLogic {
+
+    // Runs MCTS on the given tree until the deadline, returning the number of playouts
+    fn search(&mut self, root: &mut Node, team: &Team, deadline: time::Instant) -> u64 {
+        self.expand_root(root, *team);
+        let context = self.context(*team);
+        let playouts = if self.threads > 1 {
+            root.search_parallel(&context, deadline, self.threads)
+        } else {
+            root.search(&context, deadline)
+        };
+        self.tree_nodes = context.nodes.into_inner();
+        playouts
+    }
+
+    // Expands the root even if the tree is full, so that there is a move to pick
+    fn expand_root(&mut self, root: &mut Node, team: Team) {
+        if root.children.is_empty() {
+            let context = self.context(team);
+            root.expand(&context);
+            self.tree_nodes = context.nodes.into_inner();
+        }
+    }
+
+    fn context(&self, team: Team) -> Context<'_> {
+        Context {
+            team,
+            exploration_constant: self.exploration_constant,
+            evaluator: &*self.evaluator,
+            seed: self.seed,
+            nodes: AtomicUsize::new(self.tree_nodes),
+            max_nodes: self.tree_memory / std::mem::size_of::<Node>(),
+        }
+    }
+
+    // Takes the node of the given state out of the game tree, which is either the
+    // root or one of the next two plies, or creates a new one if there is none
+    fn take_subtree(&mut self, state: &State) -> Node {
+        let matches = |node: &Node| node.state.hash() == state.hash() && node.state == *state;
+        if let Some(game_tree) = self.game_tree.take() {
+            if matches(&game_tree) {
+                return game_tree;
+            }
+            for child in game_tree.children {
+                let node = if matches(&child) {
+                    Some(child)
+                } else {
+                    child.children.into_iter().find(|n| matches(n))
+                };
+                if let Some(node) = node {
+                    self.tree_nodes = node.size();
+                    return node;
+                }
+            }
+        }
+        self.tree_nodes = 1;
+        Node::new(*state)
+    }
+
+}
+
+/// What the nodes need to know during a search, one per search thread.
+struct Context<'a> {
+    team: Team,
+    exploration_constant: f64,
+    evaluator: &'a dyn Evaluator,
+    seed: Option<u64>,
+    /// The number of nodes in the tree, which stops growing at `max_nodes`.
+    nodes: AtomicUsize,
+    max_nodes: usize,
+}
+
+impl Context<'_> {
+    fn is_full(&self) -> bool {
+        self.nodes.load(Ordering::Relaxed) >= self.max_nodes
+    }
 
-    fn on_update_state(&mut self, state: &State) { debug!("Board:\n{}", state.board()) }
-    
 }
 
 #[derive(Clone)]
@@ -86,33 +248,116 @@ impl Node {
         }
     }
 
-    fn mcts(&mut self, team: &Team) -> (f64,bool) {
+    // Runs MCTS until the deadline, returning the number of playouts
+    fn search(&mut self, context: &Context, deadline: time::Instant) -> u64 {
+        let mut playouts = 0;
+        while time::Instant::now() < deadline && !self.fully_expanded {
+            self.mcts(context);
+            playouts += 1;
+        }
+        playouts
+    }
+
+    // Runs MCTS with root parallelism. Every thread searches its own copy of the tree
+    // with its own seed, so that the threads order the children differently and explore
+    // different lines. Afterwards the visits and totals of each move are summed up in the
+    // copy of the first thread, which is kept as the tree.
+    fn search_parallel(&mut self, context: &Context, deadline: time::Instant, threads: usize) -> u64 {
+        let threads = threads.max(1);
+        let base: Vec<(u32, f64)> = self.children.iter().map(|c| (c.visits, c.total)).collect();
+        // Every copy may grow by its share of the remaining memory
+        let nodes = context.nodes.load(Ordering::Relaxed);
+        let growth = context.max_nodes.saturating_sub(nodes) / threads;
+        let contexts: Vec<Context> = (0..threads).map(|i| Context {
+            seed: if i == 0 { context.seed } else { Some(context.seed.unwrap_or(0) ^ (i as u64).wrapping_mul(SEED_STEP)) },
+            nodes: AtomicUsize::new(nodes),
+            max_nodes: nodes + growth,
+            ..*context
+        }).collect();
+        let mut roots: Vec<Node> = contexts.iter().skip(1).map(|context| {
+            let mut root = self.clone();
+            root.order_children(context.seed.unwrap());
+            root
+        }).collect();
+        roots.insert(0, std::mem::replace(self, Node::new(self.state)));
+        for root in roots.iter_mut() {
+            // At least one visit, so that no playout is spent on rolling out the root itself
+            root.visits = root.visits.max(1);
+        }
+
+        let playouts = thread::scope(|scope| roots.iter_mut()
+            .zip(&contexts)
+            .map(|(root, context)| scope.spawn(move || root.search(context, deadline)))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().expect("Search thread panicked"))
+            .sum());
+
+        let mut roots = roots.into_iter();
+        *self = roots.next().unwrap();
+        for root in roots {
+            for child in root.children {
+                let i = self.children.iter().position(|c| c.state.last_move() == child.state.last_move()).unwrap();
+                let (visits, total) = base[i];
+                let merged = &mut self.children[i];
+                merged.visits += child.visits - visits;
+                merged.total += child.total - total;
+                merged.fully_expanded |= child.fully_expanded;
+            }
+        }
+        self.visits = self.children.iter().map(|c| c.visits).sum();
+        self.total = self.children.iter().map(|c| c.total).sum();
+        self.fully_expanded = self.children.iter().all(|c| c.fully_expanded);
+        context.nodes.store(contexts[0].nodes.load(Ordering::Relaxed), Ordering::Relaxed);
+        playouts
+    }
+
+    // The child with the highest average reward
+    fn best_child(&self) -> &Node {
+        self.children.iter().max_by_key(|c| ((c.total/c.visits as f64)*1000000.) as i32).unwrap()
+    }
+
+    // How many more playouts the best child has than any other child,
+    // zero if it is not the most visited one
+    fn lead(&self) -> u64 {
+        let best = self.best_child();
+        let runner_up = self.children.iter().filter(|c| !std::ptr::eq(*c, best)).map(|c| c.visits).max().unwrap_or(0);
+        best.visits.saturating_sub(runner_up) as u64
+    }
+
+    // The number of nodes in the subtree of this node
+    fn size(&self) -> usize {
+        1 + self.children.iter().map(Node::size).sum::<usize>()
+    }
+
+    fn mcts(&mut self, context: &Context) -> (f64,bool) {
         let result;
-        if self.visits > 0 && !self.state.is_terminal() {
+        // Once the tree is full, leaves are only rolled out again
+        if self.visits > 0 && !self.state.is_terminal() && (!self.children.is_empty() || !context.is_full()) {
             if self.children.is_empty() {
-                self.expand();
+                self.expand(context);
             }
-            let selected_child = self.select_child(team);
+            let selected_child = self.select_child(&context.team, context.exploration_constant);
             let fully_expanded;
-            (result, fully_expanded) = selected_child.mcts(team);
+            (result, fully_expanded) = selected_child.mcts(context);
             if fully_expanded {self.fully_expanded = self.children.iter().all(|c| c.fully_expanded);}
         } else {
-            result = self.rollout(team);
+            result = self.rollout(&context.team, context.evaluator);
             self.fully_expanded = self.state.is_terminal();
         }
         self.visits += 1;
         self.total += result;
-        return (result, self.fully_expanded);
+        (result, self.fully_expanded)
     }
     
     // Selects the best child node based on the UCB1 formula
-    fn select_child(&mut self, my_team: &Team) -> &mut Node {
+    fn select_child(&mut self, my_team: &Team, exploration_constant: f64) -> &mut Node {
         let mut best_score = f64::MIN;
         let mut best_child = None;
         for child in self.children.iter_mut().filter(|c| !c.fully_expanded) {
             let score = if child.visits > 0 {
-                let winrate = if self.state.current_team() == *my_team {child.total / child.visits as f64} else {-1.* (child.total / child.visits as f64)};
-                winrate + EXPLORATION_CONSTANT * ((self.visits as f64).ln() / (child.visits as f64)).sqrt()
+                let winrate = if self.state.current_team() == *my_team {child.total / child.visits as f64} else {-(child.total / child.visits as f64)};
+                winrate + exploration_constant * ((self.visits as f64).ln() / (child.visits as f64)).sqrt()
             } else {
                 f64::MAX
             };
@@ -124,51 +369,109 @@ impl Node {
         best_child.unwrap()
     }
 
-    // Expands the node by creating a child node for each possible move
-    fn expand(&mut self) {
+    // Expands the node by creating a child node for each possible move,
+    // shuffled by the seed if there is one
+    fn expand(&mut self, context: &Context) {
         for m in self.state.possible_moves() {
-            let mut next_state = self.state.clone();
+            let mut next_state = self.state;
             next_state.perform(m);
             self.children.push(Node::new(next_state));
         }
+        if let Some(seed) = context.seed {
+            self.order_children(seed);
+        }
+        context.nodes.fetch_add(self.children.len(), Ordering::Relaxed);
     }
 
-    // Heuristic to predict the outcoming score of a game using Breadth-First-Search
-    fn rollout(&mut self, my_team: &Team) -> f64 {
-        let s = self.state;
-        let fishes = |team: Team| -> [u8; 64] { 
-            let mut steps: [u8; 64] = [u8::MAX; 64];
-            let mut queue: Vec<Vec2<Doubled>> = s.board().penguins().filter(|p| p.1 == team).map(|p| p.0).collect();
-            let mut visited: HashSet<Vec2<Doubled>> = HashSet::new();
-            let mut step = 2;
-            while !queue.is_empty() {
-                let mut temp: Vec<Vec2<Doubled>> = Vec::new();
-                for f in queue {
-                    for m in s.board().possible_moves_from(f) {
-                        if !visited.contains(&m.to()) {
-                            visited.insert(m.to());
-                            steps[(m.to().y*8+m.to().x/2) as usize] = step;
-                            temp.push(m.to());
-                        }
-                    }
-                }
-                queue = temp;
-                step += 1;
-            }
-            steps
-        };
-        let steps_us = fishes(*my_team);
-        let steps_opponent = fishes(my_team.opponent());
-        let mut fish_us = s.fish(*my_team) as f64;
-        let mut fish_opponent = s.fish(my_team.opponent()) as f64;
-        for (c,f) in s.board().fields() {
-            if f.is_empty() {continue;}
-            let i = (c.y*8+c.x/2) as usize;
-            let fish = f.fish() as f64;
-            if steps_us[i] > steps_opponent[i] {fish_opponent += fish;} else if steps_us[i] < steps_opponent[i] {fish_us += fish;}
+    // Shuffles the children by the given seed
+    fn order_children(&mut self, seed: u64) {
+        self.children.sort_by_cached_key(|c| (c.state.hash() ^ seed).wrapping_mul(SEED_STEP).rotate_left(29));
+    }
+
+    // Estimates the outcome of the game from the current state
+    fn rollout(&mut self, my_team: &Team, evaluator: &dyn Evaluator) -> f64 {
+        evaluator.evaluate(&self.state, *my_team)
+    }
+
+}
+#[cfg(test)]
+mod tests {
+    use std::time;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{client::GameClientDelegate, game::{Board, State, Team}, search::TimeManager};
+
+    use super::{Node, OwnLogic};
+
+    #[test]
+    fn test_search_parallel() {
+        let board = Board::generate(&mut StdRng::seed_from_u64(0));
+        let logic = OwnLogic::default();
+        let context = logic.context(Team::One);
+        let mut root = Node::new(State::new(board, 0, [0, 0], None, Team::One));
+        root.expand(&context);
+        let moves: Vec<_> = root.children.iter().map(|c| c.state.last_move()).collect();
+
+        let deadline = time::Instant::now() + time::Duration::from_millis(50);
+        let playouts = root.search_parallel(&context, deadline, 3);
+
+        assert!(playouts > 0);
+        assert_eq!(root.children.iter().map(|c| c.state.last_move()).collect::<Vec<_>>(), moves);
+        assert_eq!(root.visits as u64, playouts);
+
+        // The playouts of all threads add up on top of the existing tree
+        let visits = root.visits as u64;
+        let deadline = time::Instant::now() + time::Duration::from_millis(50);
+        let playouts = root.search_parallel(&context, deadline, 3);
+        assert_eq!(root.visits as u64, visits + playouts);
+        assert_eq!(root.children.iter().map(|c| c.state.last_move()).collect::<Vec<_>>(), moves);
+    }
+
+    #[test]
+    fn test_ponder() {
+        let board = Board::generate(&mut StdRng::seed_from_u64(0));
+        let state = State::new(board, 0, [0, 0], None, Team::One);
+        let mut logic = OwnLogic { time_manager: TimeManager::fixed(time::Duration::from_millis(50)), ..Default::default() };
+
+        // Team Two ponders while Team One is to move, growing the same tree
+        let mut visits = 0;
+        for _ in 0..3 {
+            assert!(logic.ponder(&state, Team::Two));
+            let root = logic.game_tree.as_ref().unwrap();
+            assert!(root.state == state && root.visits > visits);
+            visits = root.visits;
         }
-        let result = fish_us - fish_opponent;
-        return result;
+
+        // After the opponent's move the pondered subtree is reused
+        let next = logic.game_tree.as_ref().unwrap().children[0].state;
+        let pondered = logic.game_tree.as_ref().unwrap().children[0].visits;
+        let m = logic.request_move(&next, Team::Two);
+        assert!(next.validate(m).is_ok());
+        assert!(logic.game_tree.as_ref().unwrap().visits > pondered);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_tree_memory() {
+        let board = Board::generate(&mut StdRng::seed_from_u64(0));
+        let state = State::new(board, 0, [0, 0], None, Team::One);
+        let max_nodes = 500;
+        let mut logic = OwnLogic {
+            time_manager: TimeManager::fixed(time::Duration::from_millis(50)),
+            tree_memory: max_nodes * std::mem::size_of::<Node>(),
+            seed: Some(1),
+            ..Default::default()
+        };
+
+        // The tree stops growing after the expansion that fills it
+        let m = logic.request_move(&state, Team::One);
+        assert!(state.validate(m).is_ok());
+        assert!(logic.playouts > max_nodes as u64);
+        assert!(logic.tree_nodes >= max_nodes && logic.tree_nodes <= max_nodes + 64);
+        assert_eq!(logic.tree_nodes, logic.game_tree.as_ref().unwrap().size());
+
+        // Without any time to search there is still a move to pick
+        let mut logic = OwnLogic { time_manager: TimeManager::fixed(time::Duration::ZERO), ..Default::default() };
+        assert!(state.validate(logic.request_move(&state, Team::One)).is_ok());
+    }
+}
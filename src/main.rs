@@ -1,12 +1,14 @@
-mod logic;
-
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 use clap::Parser;
 use simplelog::{SimpleLogger, Config};
-use log::LevelFilter;
-use socha_client_2023::client::{GameClient, DebugMode};
-
-use logic::OwnLogic;
+use log::{info, LevelFilter};
+use socha_client_2023::client::{GameClient, DebugMode, ReconnectPolicy};
+use socha_client_2023::config::{Engine, SearchConfig};
+use socha_client_2023::evaluator::EvaluatorKind;
+use socha_client_2023::protocol::Request;
+use socha_client_2023::replay::ReplayFormat;
 
 /// Software Challenge 2023 client.
 #[derive(Parser, Debug)]
@@ -17,9 +19,26 @@ struct Args {
     /// The game server's port.
     #[clap(short, long, default_value_t = 13050)]
     port: u16,
-    /// A game reservation.
+    /// A game reservation, can be given several times to play in several rooms at once.
     #[clap(short, long)]
-    reservation: Option<String>,
+    reservation: Vec<String>,
+    /// The id of a room to join, can be given several times like reservations.
+    #[clap(long)]
+    room: Vec<String>,
+    /// The total number of games to play. After a game, any open game is joined
+    /// until this many have been played.
+    #[clap(short, long, default_value_t = 1)]
+    games: usize,
+    /// The number of attempts to reconnect in a row after losing the connection.
+    #[clap(long, default_value_t = ReconnectPolicy::default().retries)]
+    retries: usize,
+    /// The time in milliseconds to wait before reconnecting, doubled with every further attempt.
+    #[clap(long, default_value_t = ReconnectPolicy::default().backoff.as_millis() as u64)]
+    backoff: u64,
+    /// The time in milliseconds after a move request by which a fallback move is sent
+    /// if the engine hasn't moved, by default halfway into the safety margin.
+    #[clap(long)]
+    move_deadline: Option<u64>,
     /// The level to log at.
     #[clap(short, long, default_value = "Info")]
     level: String,
@@ -29,6 +48,40 @@ struct Args {
     /// Prints outgoing XML messages to the console for debugging.
     #[clap(short = 'D', long)]
     debug_writer: bool,
+    /// The engine to play with.
+    #[clap(short, long, arg_enum, default_value = "mcts")]
+    engine: Engine,
+    /// Records the games to replay files in this directory.
+    #[clap(long)]
+    record: Option<PathBuf>,
+    /// The format of the recorded replays.
+    #[clap(long, arg_enum, default_value = "xml")]
+    replay_format: ReplayFormat,
+    /// A config file with one `key = value` per line for the search
+    /// parameters below, which take precedence over it.
+    #[clap(short, long)]
+    config: Option<PathBuf>,
+    /// The evaluation function of the engine.
+    #[clap(long, arg_enum)]
+    evaluator: Option<EvaluatorKind>,
+    /// The number of threads to search with (MCTS only).
+    #[clap(short, long)]
+    threads: Option<usize>,
+    /// The server's soft timeout per move in milliseconds.
+    #[clap(long)]
+    soft_timeout: Option<u64>,
+    /// The time in milliseconds kept free for network latency.
+    #[clap(long)]
+    safety_margin: Option<u64>,
+    /// The weight of exploration in the UCB1 formula (MCTS only).
+    #[clap(long)]
+    exploration_constant: Option<f64>,
+    /// Seeds the tie-breaking between equally good moves (MCTS only).
+    #[clap(long)]
+    seed: Option<u64>,
+    /// The memory the game tree may use in MiB (MCTS only).
+    #[clap(long)]
+    tree_memory: Option<usize>,
 }
 
 fn main() {
@@ -44,6 +97,34 @@ fn main() {
         debug_writer: args.debug_writer,
     };
 
-    let client = GameClient::new(OwnLogic {game_tree: None}, debug_mode, args.reservation);
-    let _result = client.connect(&args.host, args.port).expect("Error while running client.");
+    let mut config = args.config.as_ref().map_or_else(|| Ok(SearchConfig::default()), SearchConfig::load).expect("Could not read config file.");
+    if let Some(evaluator) = args.evaluator { config.evaluator = evaluator; }
+    if let Some(threads) = args.threads { config.threads = threads; }
+    if let Some(soft_timeout) = args.soft_timeout { config.soft_timeout = Duration::from_millis(soft_timeout); }
+    if let Some(safety_margin) = args.safety_margin { config.safety_margin = Duration::from_millis(safety_margin); }
+    if let Some(exploration_constant) = args.exploration_constant { config.exploration_constant = exploration_constant; }
+    if let Some(seed) = args.seed { config.seed = Some(seed); }
+    if let Some(tree_memory) = args.tree_memory { config.tree_memory = tree_memory; }
+    info!("Playing with {:?} and\n{}", args.engine, config);
+    let move_deadline = args.move_deadline.map_or_else(|| config.soft_timeout.saturating_sub(config.safety_margin / 2), Duration::from_millis);
+
+    let engine = args.engine;
+    let factory = move || config.engine(engine);
+    let mut joins: Vec<Request> = args.reservation.into_iter().map(|reservation_code| Request::JoinPrepared { reservation_code })
+        .chain(args.room.into_iter().map(|room_id| Request::JoinRoom { room_id }))
+        .collect();
+    if joins.is_empty() {
+        joins.push(Request::Join);
+    }
+    let policy = ReconnectPolicy { retries: args.retries, backoff: Duration::from_millis(args.backoff), ..Default::default() };
+    let mut client = GameClient::with_factory(factory, debug_mode)
+        .games(args.games.max(joins.len()))
+        .joining(joins)
+        .reconnect(policy)
+        .move_deadline(move_deadline);
+    if let Some(dir) = args.record {
+        client = client.record_to(dir, args.replay_format);
+    }
+    let results = client.connect_rooms(&args.host, args.port).expect("Error while running client.");
+    info!("Played {} games", results.len());
 }
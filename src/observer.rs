@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::net::TcpStream;
+use log::{info, warn, error};
+use quick_xml::events::{Event as XmlEvent, BytesStart};
+use quick_xml::{Reader, Writer};
+use crate::game::State;
+use crate::protocol::{Event, EventPayload, GameResult, Request, Slot};
+use crate::util::{Element, Error, Result};
+
+/// A client speaking the administrator protocol, which prepares
+/// games on the server, observes them and controls their course.
+pub struct ObserverClient<R, W> where R: BufRead, W: Write {
+    reader: Reader<R>,
+    writer: Writer<W>,
+    /// Events received while waiting for the answer to a request.
+    pending: VecDeque<Event>,
+}
+
+impl ObserverClient<BufReader<TcpStream>, BufWriter<TcpStream>> {
+    /// Connects to the server via TCP and authenticates with the password.
+    pub fn connect(host: &str, port: u16, password: &str) -> Result<Self> {
+        let address = format!("{}:{}", host, port);
+        let stream = TcpStream::connect(&address)?;
+        info!("Connected to {}", address);
+        Self::new(BufReader::new(stream.try_clone()?), BufWriter::new(stream), password)
+    }
+}
+
+impl<R, W> ObserverClient<R, W> where R: BufRead, W: Write {
+    /// Performs the handshake on the given streams and authenticates with the password.
+    pub fn new(read: R, write: W, password: &str) -> Result<Self> {
+        let mut reader = Reader::from_reader(read);
+        let mut writer = Writer::new(write);
+
+        // Write <protocol>
+        writer.write_event(XmlEvent::Start(BytesStart::borrowed_name(b"protocol")))?;
+
+        Element::from(Request::Authenticate { password: password.to_owned() }).write_to(&mut writer)?;
+
+        // Read <protocol>
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event(&mut buf)? {
+                XmlEvent::Start(ref start) if start.name() == b"protocol" => break,
+                XmlEvent::Text(_) | XmlEvent::Decl(_) => (),
+                XmlEvent::Eof => return Err(Error::Eof),
+                e => warn!("Got unexpected event {:?}", e),
+            }
+        }
+        Ok(Self { reader, writer, pending: VecDeque::new() })
+    }
+
+    /// Sends a request to the server.
+    pub fn send(&mut self, request: Request) -> Result<()> {
+        Element::from(request).write_to(&mut self.writer)
+    }
+
+    /// Waits for the next event, skipping messages that can't be parsed.
+    pub fn receive(&mut self) -> Result<Event> {
+        if let Some(event) = self.pending.pop_front() {
+            return Ok(event);
+        }
+        self.read()
+    }
+
+    fn read(&mut self) -> Result<Event> {
+        loop {
+            let event_xml = Element::read_from(&mut self.reader)?;
+            match Event::try_from(&event_xml) {
+                Ok(event) => return Ok(event),
+                Err(Error::ServerError(message)) => error!("Server error: {}", message),
+                Err(e) => warn!("Error while parsing event: {:?}", e),
+            }
+        }
+    }
+
+    /// Waits for the first event picked by the function, keeping the other events for `receive`.
+    fn wait_for<T>(&mut self, mut pick: impl FnMut(&Event) -> Option<T>) -> Result<T> {
+        loop {
+            let event = self.read()?;
+            match pick(&event) {
+                Some(value) => return Ok(value),
+                None => self.pending.push_back(event),
+            }
+        }
+    }
+
+    /// Prepares a game with a slot per team, returning the room id and the
+    /// reservation codes of the slots. A paused game waits for `pause` or `step`.
+    pub fn prepare(&mut self, slots: Vec<Slot>, pause: bool) -> Result<(String, Vec<String>)> {
+        self.send(Request::Prepare { slots, pause })?;
+        self.wait_for(|event| match event {
+            Event::Prepared { room_id, reservations } => Some((room_id.clone(), reservations.clone())),
+            _ => None,
+        })
+    }
+
+    /// Subscribes to the messages of the room.
+    pub fn observe(&mut self, room_id: &str) -> Result<()> {
+        self.send(Request::Observe { room_id: room_id.to_owned() })?;
+        self.wait_for(|event| matches!(event, Event::Observed { room_id: r } if r == room_id).then_some(()))
+    }
+
+    /// Pauses or resumes the game in the room.
+    pub fn pause(&mut self, room_id: &str, pause: bool) -> Result<()> {
+        self.send(Request::Pause { room_id: room_id.to_owned(), pause })
+    }
+
+    /// Lets the paused game in the room proceed by one move.
+    pub fn step(&mut self, room_id: &str) -> Result<()> {
+        self.send(Request::Step { room_id: room_id.to_owned() })
+    }
+
+    /// Ends the game in the room.
+    pub fn cancel(&mut self, room_id: &str) -> Result<()> {
+        self.send(Request::Cancel { room_id: room_id.to_owned() })
+    }
+
+    /// Waits for the next state of the observed room.
+    pub fn next_state(&mut self, room_id: &str) -> Result<State> {
+        loop {
+            if let Event::Room { room_id: r, payload: EventPayload::Memento(state) } = self.receive()? {
+                if r == room_id {
+                    return Ok(state);
+                }
+            }
+        }
+    }
+
+    /// Passes the states of the observed room to the function until the game
+    /// is over, returning its result or none if the game was cancelled.
+    pub fn watch(&mut self, room_id: &str, mut on_state: impl FnMut(&State)) -> Result<Option<GameResult>> {
+        let mut result = None;
+        loop {
+            match self.receive()? {
+                Event::Room { room_id: r, payload } if r == room_id => match payload {
+                    EventPayload::Memento(state) => on_state(&state),
+                    EventPayload::GameResult(game_result) => result = Some(game_result),
+                    _ => {},
+                },
+                Event::Left { room_id: r } if r == room_id => return Ok(result),
+                _ => {},
+            }
+        }
+    }
+}
@@ -11,6 +11,26 @@ pub enum Event {
     Left { room_id: String },
     /// A message in a room.
     Room { room_id: String, payload: EventPayload },
+    /// Notifies an administrator that a game has been prepared
+    /// with the reservation codes of its slots.
+    Prepared { room_id: String, reservations: Vec<String> },
+    /// Notifies an administrator that they observe a room.
+    Observed { room_id: String },
+}
+
+impl From<&Event> for Element {
+    fn from(event: &Event) -> Self {
+        match event {
+            Event::Joined { room_id } => Element::new("joined").attribute("roomId", room_id).build(),
+            Event::Left { room_id } => Element::new("left").attribute("roomId", room_id).build(),
+            Event::Room { room_id, payload } => Element::new("room").attribute("roomId", room_id).child(payload).build(),
+            Event::Prepared { room_id, reservations } => Element::new("prepared")
+                .attribute("roomId", room_id)
+                .childs(reservations.iter().map(|r| Element::new("reservation").content(r).build()))
+                .build(),
+            Event::Observed { room_id } => Element::new("observed").attribute("roomId", room_id).build(),
+        }
+    }
 }
 
 impl TryFrom<&Element> for Event {
@@ -24,6 +44,11 @@ impl TryFrom<&Element> for Event {
                 room_id: elem.attribute("roomId")?.to_owned(),
                 payload: elem.child_by_name("data")?.try_into()?,
             }),
+            "prepared" => Ok(Self::Prepared {
+                room_id: elem.attribute("roomId")?.to_owned(),
+                reservations: elem.childs_by_name("reservation").map(|r| r.content().to_owned()).collect(),
+            }),
+            "observed" => Ok(Self::Observed { room_id: elem.attribute("roomId")?.to_owned() }),
             _ => Err(Error::UnknownElement(elem.clone())),
         }
     }
@@ -32,6 +32,17 @@ impl fmt::Display for EventPayload {
     }
 }
 
+impl From<&EventPayload> for Element {
+    fn from(payload: &EventPayload) -> Self {
+        match payload {
+            EventPayload::Welcome(team) => Element::new("data").attribute("class", "welcomeMessage").attribute("color", team).build(),
+            EventPayload::Memento(state) => Element::new("data").attribute("class", "memento").child(state).build(),
+            EventPayload::MoveRequest => Element::new("data").attribute("class", "moveRequest").build(),
+            EventPayload::GameResult(result) => result.into(),
+        }
+    }
+}
+
 impl TryFrom<&Element> for EventPayload {
     type Error = Error;
 
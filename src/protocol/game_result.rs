@@ -1,8 +1,8 @@
-use std::collections::HashMap;
+use std::{cmp::Ordering, collections::HashMap};
 
-use crate::util::{Element, Error, Result};
+use crate::{util::{Element, Error, Result}, game::{State, Team}};
 
-use super::{ScoreDefinition, Player, Score};
+use super::{ScoreDefinition, ScoreDefinitionFragment, ScoreAggregation, Player, Score, ScoreCause};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct GameResult {
@@ -17,6 +17,39 @@ impl GameResult {
         Self { definition, scores: scores.into(), winner }
     }
 
+    /// Scores a game that ended in the given state like the official server does.
+    /// If a team broke the rules (or timed out, left, ...), it loses regardless of fish.
+    pub fn scored(state: &State, violation: Option<(Team, ScoreCause, &str)>) -> Self {
+        let winner = match violation {
+            Some((team, _, _)) => Some(team.opponent()),
+            None => match state.fish(Team::One).cmp(&state.fish(Team::Two)) {
+                Ordering::Greater => Some(Team::One),
+                Ordering::Less => Some(Team::Two),
+                Ordering::Equal => None,
+            },
+        };
+        let scores = [Team::One, Team::Two].map(|team| {
+            let points = match winner {
+                Some(w) if w == team => 2,
+                Some(_) => 0,
+                None => 1,
+            };
+            let (cause, reason) = match violation {
+                Some((t, cause, reason)) if t == team => (cause, reason),
+                _ => (ScoreCause::Regular, ""),
+            };
+            (Player::new(None, team), Score::new(cause, reason, [points, state.fish(team) as i32]))
+        });
+        Self::new(
+            ScoreDefinition::new([
+                ScoreDefinitionFragment::new("Siegpunkte", ScoreAggregation::Sum, true),
+                ScoreDefinitionFragment::new("∅ Punkte", ScoreAggregation::Average, true),
+            ]),
+            scores,
+            winner.map(|t| Player::new(None, t)),
+        )
+    }
+
     #[inline]
     pub fn definition(&self) -> &ScoreDefinition { &self.definition }
 
@@ -27,6 +60,24 @@ impl GameResult {
     pub fn winner(&self) -> &Option<Player> { &self.winner }
 }
 
+impl From<&GameResult> for Element {
+    fn from(result: &GameResult) -> Self {
+        // Sort the entries to get a deterministic output
+        let mut scores: Vec<_> = result.scores.iter().collect();
+        scores.sort_by_key(|(p, _)| p.team().index());
+        Element::new("data")
+            .attribute("class", "result")
+            .child(&result.definition)
+            .child(Element::new("scores")
+                .childs(scores.into_iter().map(|(player, score)| Element::new("entry")
+                    .child(player)
+                    .child(score)
+                    .build())))
+            .option_child(result.winner.as_ref().map(|w| Element::new("winner").attribute("team", w.team())))
+            .build()
+    }
+}
+
 impl TryFrom<&Element> for GameResult {
     type Error = Error;
 
@@ -99,4 +150,20 @@ mod tests {
             Some(Player::new(None, Team::One))
         ));
     }
+
+    #[test]
+    fn test_to_xml() {
+        let result = GameResult::new(
+            ScoreDefinition::new([
+                ScoreDefinitionFragment::new("Siegpunkte", ScoreAggregation::Sum, true),
+                ScoreDefinitionFragment::new("∅ Punkte", ScoreAggregation::Average, true),
+            ]),
+            hashmap![
+                Player::new(Some("rad"), Team::One) => Score::new(ScoreCause::Regular, "", [2, 27]),
+                Player::new(None, Team::Two) => Score::new(ScoreCause::Left, "Player left", [0, 15])
+            ],
+            Some(Player::new(None, Team::One))
+        );
+        assert_eq!(GameResult::try_from(&Element::from(&result)).unwrap(), result);
+    }
 }
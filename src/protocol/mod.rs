@@ -9,6 +9,7 @@ mod score_aggregation;
 mod score_cause;
 mod score_definition;
 mod score_definition_fragment;
+mod slot;
 
 pub use event::*;
 pub use request::*;
@@ -21,3 +22,4 @@ pub use score_aggregation::*;
 pub use score_cause::*;
 pub use score_definition::*;
 pub use score_definition_fragment::*;
+pub use slot::*;
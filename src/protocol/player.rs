@@ -13,12 +13,22 @@ impl Player {
     }
 
     #[inline]
-    pub fn name(&self) -> Option<&str> { self.name.as_ref().map(|n| n.as_str()) }
+    pub fn name(&self) -> Option<&str> { self.name.as_deref() }
 
     #[inline]
     pub fn team(&self) -> Team { self.team }
 }
 
+impl From<&Player> for Element {
+    fn from(player: &Player) -> Self {
+        let mut builder = Element::new("player").attribute("team", player.team);
+        if let Some(name) = &player.name {
+            builder = builder.attribute("name", name);
+        }
+        builder.build()
+    }
+}
+
 impl TryFrom<&Element> for Player {
     type Error = Error;
 
@@ -48,4 +58,14 @@ mod tests {
             <player team="TWO" />
         "#}).unwrap()).unwrap(), Player::new(None, Team::Two));
     }
+
+    #[test]
+    fn test_to_xml() {
+        assert_eq!(Element::from(&Player::new(Some("Alice"), Team::One)), Element::from_str(indoc! {r#"
+            <player name="Alice" team="ONE" />
+        "#}).unwrap());
+
+        let player = Player::new(None, Team::Two);
+        assert_eq!(Player::try_from(&Element::from(&player)).unwrap(), player);
+    }
 }
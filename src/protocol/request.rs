@@ -1,6 +1,6 @@
-use crate::util::Element;
+use crate::util::{Element, Error, Result};
 
-use super::RequestPayload;
+use super::{RequestPayload, Slot};
 
 const GAME_TYPE: &str = "swc_2023_penguins";
 
@@ -16,6 +16,19 @@ pub enum Request {
     JoinPrepared { reservation_code: String },
     /// A message in a room.
     Room { room_id: String, payload: RequestPayload },
+    /// Authenticates the client as an administrator.
+    Authenticate { password: String },
+    /// Prepares a game with reserved slots for the players,
+    /// optionally paused until the administrator resumes it.
+    Prepare { slots: Vec<Slot>, pause: bool },
+    /// Subscribes to the messages of the given room.
+    Observe { room_id: String },
+    /// Pauses or resumes the game in the given room.
+    Pause { room_id: String, pause: bool },
+    /// Lets the paused game in the given room proceed by one move.
+    Step { room_id: String },
+    /// Ends the game in the given room.
+    Cancel { room_id: String },
 }
 
 impl From<Request> for Element {
@@ -25,6 +38,42 @@ impl From<Request> for Element {
             Request::JoinRoom { room_id } => Element::new("joinRoom").attribute("roomId", room_id).build(),
             Request::JoinPrepared { reservation_code } => Element::new("joinPrepared").attribute("reservationCode", reservation_code).build(),
             Request::Room { room_id, payload } => Element::new("room").attribute("roomId", room_id).child(payload).build(),
+            Request::Authenticate { password } => Element::new("authenticate").attribute("password", password).build(),
+            Request::Prepare { slots, pause } => Element::new("prepare")
+                .attribute("gameType", GAME_TYPE)
+                .attribute("pause", pause)
+                .childs(slots.iter().map(Element::from))
+                .build(),
+            Request::Observe { room_id } => Element::new("observe").attribute("roomId", room_id).build(),
+            Request::Pause { room_id, pause } => Element::new("pause").attribute("roomId", room_id).attribute("pause", pause).build(),
+            Request::Step { room_id } => Element::new("step").attribute("roomId", room_id).build(),
+            Request::Cancel { room_id } => Element::new("cancel").attribute("roomId", room_id).build(),
+        }
+    }
+}
+
+impl TryFrom<&Element> for Request {
+    type Error = Error;
+
+    fn try_from(elem: &Element) -> Result<Self> {
+        match elem.name() {
+            "join" => Ok(Self::Join),
+            "joinRoom" => Ok(Self::JoinRoom { room_id: elem.attribute("roomId")?.to_owned() }),
+            "joinPrepared" => Ok(Self::JoinPrepared { reservation_code: elem.attribute("reservationCode")?.to_owned() }),
+            "room" => Ok(Self::Room {
+                room_id: elem.attribute("roomId")?.to_owned(),
+                payload: elem.child_by_name("data")?.try_into()?,
+            }),
+            "authenticate" => Ok(Self::Authenticate { password: elem.attribute("password")?.to_owned() }),
+            "prepare" => Ok(Self::Prepare {
+                slots: elem.childs_by_name("slot").map(Slot::try_from).collect::<Result<_>>()?,
+                pause: elem.attribute("pause")?.parse()?,
+            }),
+            "observe" => Ok(Self::Observe { room_id: elem.attribute("roomId")?.to_owned() }),
+            "pause" => Ok(Self::Pause { room_id: elem.attribute("roomId")?.to_owned(), pause: elem.attribute("pause")?.parse()? }),
+            "step" => Ok(Self::Step { room_id: elem.attribute("roomId")?.to_owned() }),
+            "cancel" => Ok(Self::Cancel { room_id: elem.attribute("roomId")?.to_owned() }),
+            _ => Err(Error::UnknownElement(elem.clone())),
         }
     }
 }
@@ -1,4 +1,4 @@
-use crate::{util::Element, game::Move};
+use crate::{util::{Element, Error, Result}, game::Move};
 
 /// The data of a room message to the server.
 #[derive(Debug, Clone)]
@@ -14,3 +14,14 @@ impl From<RequestPayload> for Element {
         }
     }
 }
+
+impl TryFrom<&Element> for RequestPayload {
+    type Error = Error;
+
+    fn try_from(elem: &Element) -> Result<Self> {
+        match elem.attribute("class")? {
+            "move" => Ok(Self::Move(elem.try_into()?)),
+            _ => Err(Error::UnknownElement(elem.clone())),
+        }
+    }
+}
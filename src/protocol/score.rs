@@ -25,6 +25,16 @@ impl Score {
     pub fn parts(&self) -> &Vec<i32> { &self.parts }
 }
 
+impl From<&Score> for Element {
+    fn from(score: &Score) -> Self {
+        Element::new("score")
+            .attribute("cause", score.cause)
+            .attribute("reason", &score.reason)
+            .childs(score.parts.iter().map(|p| Element::new("part").content(&p.to_string()).build()))
+            .build()
+    }
+}
+
 impl TryFrom<&Element> for Score {
     type Error = Error;
 
@@ -54,4 +64,16 @@ mod tests {
             </score>
         "#}).unwrap()).unwrap(), Score::new(ScoreCause::Left, "Player left", [0, 15]));
     }
+
+    #[test]
+    fn test_to_xml() {
+        let score = Score::new(ScoreCause::SoftTimeout, "Too slow", [0, 12]);
+        assert_eq!(Element::from(&score), Element::from_str(indoc! {r#"
+            <score cause="SOFT_TIMEOUT" reason="Too slow">
+                <part>0</part>
+                <part>12</part>
+            </score>
+        "#}).unwrap());
+        assert_eq!(Score::try_from(&Element::from(&score)).unwrap(), score);
+    }
 }
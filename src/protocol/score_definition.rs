@@ -16,6 +16,14 @@ impl ScoreDefinition {
     pub fn fragments(&self) -> &Vec<ScoreDefinitionFragment> { &self.fragments }
 }
 
+impl From<&ScoreDefinition> for Element {
+    fn from(definition: &ScoreDefinition) -> Self {
+        Element::new("definition")
+            .childs(definition.fragments.iter().map(Element::from))
+            .build()
+    }
+}
+
 impl TryFrom<&Element> for ScoreDefinition {
     type Error = Error;
 
@@ -52,4 +60,13 @@ mod tests {
             ScoreDefinitionFragment::new("∅ Punkte", ScoreAggregation::Average, true),
         ]));
     }
+
+    #[test]
+    fn test_xml_roundtrip() {
+        let definition = ScoreDefinition::new([
+            ScoreDefinitionFragment::new("Siegpunkte", ScoreAggregation::Sum, true),
+            ScoreDefinitionFragment::new("∅ Punkte", ScoreAggregation::Average, false),
+        ]);
+        assert_eq!(ScoreDefinition::try_from(&Element::from(&definition)).unwrap(), definition);
+    }
 }
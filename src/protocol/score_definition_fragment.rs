@@ -24,6 +24,16 @@ impl ScoreDefinitionFragment {
     pub fn relevant_for_ranking(&self) -> bool { self.relevant_for_ranking }
 }
 
+impl From<&ScoreDefinitionFragment> for Element {
+    fn from(fragment: &ScoreDefinitionFragment) -> Self {
+        Element::new("fragment")
+            .attribute("name", &fragment.name)
+            .child(Element::new("aggregation").content(&fragment.aggregation.to_string()))
+            .child(Element::new("relevantForRanking").content(&fragment.relevant_for_ranking.to_string()))
+            .build()
+    }
+}
+
 impl TryFrom<&Element> for ScoreDefinitionFragment {
     type Error = Error;
 
@@ -0,0 +1,76 @@
+use crate::util::{Element, Error, Result};
+
+/// A player's place in a game prepared by an administrator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Slot {
+    display_name: String,
+    can_timeout: bool,
+    reserved: bool,
+}
+
+impl Slot {
+    #[inline]
+    pub fn new(display_name: &str, can_timeout: bool, reserved: bool) -> Self {
+        Self { display_name: display_name.to_owned(), can_timeout, reserved }
+    }
+
+    /// A reserved slot for a player who can time out, as in tournaments.
+    #[inline]
+    pub fn player(display_name: &str) -> Self {
+        Self::new(display_name, true, true)
+    }
+
+    #[inline]
+    pub fn display_name(&self) -> &str { self.display_name.as_str() }
+
+    #[inline]
+    pub fn can_timeout(&self) -> bool { self.can_timeout }
+
+    #[inline]
+    pub fn reserved(&self) -> bool { self.reserved }
+}
+
+impl From<&Slot> for Element {
+    fn from(slot: &Slot) -> Self {
+        Element::new("slot")
+            .attribute("displayName", &slot.display_name)
+            .attribute("canTimeout", slot.can_timeout)
+            .attribute("reserved", slot.reserved)
+            .build()
+    }
+}
+
+impl TryFrom<&Element> for Slot {
+    type Error = Error;
+
+    fn try_from(elem: &Element) -> Result<Self> {
+        Ok(Slot {
+            display_name: elem.attribute("displayName")?.to_owned(),
+            can_timeout: elem.attribute("canTimeout")?.parse()?,
+            reserved: elem.attribute("reserved")?.parse()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use indoc::indoc;
+
+    use crate::{util::Element, protocol::{Request, Slot}};
+
+    #[test]
+    fn test_prepare_xml() {
+        let xml = Element::from_str(indoc! {r#"
+            <prepare gameType="swc_2023_penguins" pause="true">
+                <slot displayName="One" canTimeout="true" reserved="true" />
+                <slot displayName="Two" canTimeout="false" reserved="true" />
+            </prepare>
+        "#}).unwrap();
+        let Request::Prepare { slots, pause } = Request::try_from(&xml).unwrap() else { panic!("Not a prepare request") };
+        assert!(pause);
+        assert_eq!(slots, vec![Slot::player("One"), Slot::new("Two", false, true)]);
+        assert_eq!(Element::from(Request::Prepare { slots, pause }), xml);
+    }
+}
@@ -0,0 +1,243 @@
+use std::{fs::{self, File}, io::{BufWriter, Write}, path::{Path, PathBuf}, str::FromStr, time::SystemTime};
+
+use clap::ArgEnum;
+use quick_xml::Reader;
+
+use crate::{game::{Move, State, Team}, protocol::GameResult, search::SearchStats, util::{Element, Error, Result}};
+
+/// An entry of a replay.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Record {
+    /// A game state received from the server.
+    State(State),
+    /// A move of ours with the statistics of the search that picked it.
+    Move { team: Team, game_move: Move, stats: Option<SearchStats> },
+    /// The result of the game.
+    Result(GameResult),
+}
+
+impl From<&Record> for Element {
+    fn from(record: &Record) -> Self {
+        match record {
+            Record::State(state) => state.into(),
+            Record::Move { team, game_move, stats } => Element::new("search")
+                .attribute("team", team)
+                .child(*game_move)
+                .option_child(stats.as_ref())
+                .build(),
+            Record::Result(result) => result.into(),
+        }
+    }
+}
+
+impl TryFrom<&Element> for Record {
+    type Error = Error;
+
+    fn try_from(elem: &Element) -> Result<Self> {
+        match elem.name() {
+            "state" => Ok(Self::State(elem.try_into()?)),
+            "search" => Ok(Self::Move {
+                team: elem.attribute("team")?.parse()?,
+                game_move: elem.child_by_name("data")?.try_into()?,
+                stats: elem.child_by_name("stats").ok().map(SearchStats::try_from).transpose()?,
+            }),
+            "data" if elem.attribute("class")? == "result" => Ok(Self::Result(elem.try_into()?)),
+            _ => Err(Error::UnknownElement(elem.clone())),
+        }
+    }
+}
+
+/// The file formats of replays.
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayFormat {
+    /// A `<protocol>` document of the bare states, our moves with their
+    /// statistics in `<search>` elements and the result. Unlike the replays of
+    /// the official server, the states are not wrapped in rooms, so only
+    /// `Replay` can load it.
+    Xml,
+    /// One record per line, which stays readable if the game is cut off.
+    Lines,
+}
+
+impl ReplayFormat {
+    /// The file extension of the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Xml => "xml",
+            Self::Lines => "replay",
+        }
+    }
+}
+
+/// Writes the records of a game to a replay file as they happen.
+pub struct ReplayRecorder {
+    writer: BufWriter<File>,
+    format: ReplayFormat,
+    path: PathBuf,
+}
+
+impl ReplayRecorder {
+    /// Creates a replay file in the given directory, named by the room id and the
+    /// current time, e.g. `<room id>-<seconds since the epoch>.xml`.
+    pub fn create(dir: impl AsRef<Path>, room_id: &str, format: ReplayFormat) -> Result<Self> {
+        let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        let path = dir.as_ref().join(format!("{}-{}.{}", room_id, timestamp, format.extension()));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        if format == ReplayFormat::Xml {
+            writeln!(writer, "<protocol>")?;
+        }
+        Ok(Self { writer, format, path })
+    }
+
+    /// The path of the replay file.
+    pub fn path(&self) -> &Path { &self.path }
+
+    /// Appends the record to the file.
+    pub fn record(&mut self, record: &Record) -> Result<()> {
+        let indent = if self.format == ReplayFormat::Xml { "  " } else { "" };
+        writeln!(self.writer, "{}{}", indent, Element::from(record))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Completes the file.
+    pub fn finish(mut self) -> Result<()> {
+        if self.format == ReplayFormat::Xml {
+            writeln!(self.writer, "</protocol>")?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A game loaded from a replay file, either one of ours in any format
+/// or an official one with the states wrapped in rooms or not.
+#[derive(Debug, Clone, Default)]
+pub struct Replay {
+    /// The states of the game in order.
+    pub states: Vec<State>,
+    /// The statistics of our search per state, if we recorded one.
+    pub stats: Vec<Option<SearchStats>>,
+    /// The result of the game, if it was recorded.
+    pub result: Option<GameResult>,
+}
+
+impl Replay {
+    /// Reads the replay file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// The move played in the state with the given index, if there is a next state.
+    pub fn move_at(&self, index: usize) -> Option<Move> {
+        self.states.get(index + 1).and_then(State::last_move)
+    }
+
+    fn add(&mut self, elem: &Element) -> Result<()> {
+        match elem.name() {
+            "protocol" | "room" => elem.childs().iter().try_for_each(|c| self.add(c)),
+            "data" if elem.attribute("class")? == "memento" => self.add(elem.child_by_name("state")?),
+            "state" | "search" | "data" => {
+                match elem.try_into() {
+                    Ok(Record::State(state)) => {
+                        self.states.push(state);
+                        self.stats.push(None);
+                    },
+                    Ok(Record::Move { stats, .. }) => if let Some(last) = self.stats.last_mut() {
+                        *last = stats;
+                    },
+                    Ok(Record::Result(result)) => self.result = Some(result),
+                    // Other messages of the server, e.g. move requests
+                    Err(Error::UnknownElement(_)) => {},
+                    Err(e) => return Err(e),
+                }
+                Ok(())
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromStr for Replay {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut reader = Reader::from_str(s);
+        let mut replay = Self::default();
+        loop {
+            match Element::read_from(&mut reader) {
+                Ok(elem) => replay.add(&elem)?,
+                Err(Error::Eof) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        if replay.states.is_empty() {
+            return Err(Error::InvalidState("Replay contains no states".to_owned()));
+        }
+        Ok(replay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, time::Duration};
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{game::{Board, State, Team}, protocol::GameResult, search::SearchStats, util::Element};
+
+    use super::{Record, Replay, ReplayFormat, ReplayRecorder};
+
+    #[test]
+    fn test_record() {
+        let mut state = State::new(Board::generate(&mut StdRng::seed_from_u64(0)), 0, [0, 0], None, Team::One);
+        let game_move = state.possible_moves()[0];
+        let stats = SearchStats { time: Duration::from_millis(120), nodes: 5000, depth: None, value: Some(1.5) };
+        let before = Record::State(state);
+        state.perform(game_move);
+        let records = [
+            before,
+            Record::Move { team: Team::One, game_move, stats: Some(stats) },
+            Record::State(state),
+            Record::Result(GameResult::scored(&state, None)),
+        ];
+
+        for format in [ReplayFormat::Xml, ReplayFormat::Lines] {
+            let mut recorder = ReplayRecorder::create(env::temp_dir(), "test-room", format).unwrap();
+            for record in &records {
+                recorder.record(record).unwrap();
+            }
+            let path = recorder.path().to_owned();
+            recorder.finish().unwrap();
+
+            let text = fs::read_to_string(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+            let replay: Replay = text.parse().unwrap();
+            assert_eq!(replay.states.len(), 2);
+            assert_eq!(replay.stats, vec![Some(stats), None]);
+            assert_eq!(replay.move_at(0), Some(game_move));
+            assert!(replay.result.is_some());
+
+            let lines: Vec<_> = text.lines().map(str::trim).filter(|l| !l.contains("protocol>")).collect();
+            assert_eq!(lines.len(), records.len());
+            for (line, record) in lines.into_iter().zip(&records) {
+                assert_eq!(Record::try_from(&line.parse::<Element>().unwrap()).unwrap(), *record);
+            }
+            assert!(path.file_name().unwrap().to_str().unwrap().starts_with("test-room-"));
+        }
+    }
+
+    #[test]
+    fn test_load_rooms() {
+        // The server's messages as a client receives them
+        let state = State::new(Board::generate(&mut StdRng::seed_from_u64(1)), 0, [0, 0], None, Team::One);
+        let text = format!(
+            r#"<protocol><room roomId="r"><data class="welcomeMessage" color="ONE"/></room><room roomId="r"><data class="memento">{}</data></room><room roomId="r"><data class="moveRequest"/></room></protocol>"#,
+            Element::from(&state),
+        );
+        let replay: Replay = text.parse().unwrap();
+        assert_eq!(replay.states, vec![state]);
+        assert!(replay.result.is_none() && replay.move_at(0).is_none());
+        assert!("<protocol></protocol>".parse::<Replay>().is_err());
+    }
+}
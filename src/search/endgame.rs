@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::game::{Board, Bitboard, BitIter, Move, State, Team, DIRECTION_COUNT, MAX_FISH, PENGUINS_PER_TEAM, TEAMS, slide};
+
+use super::TranspositionTable;
+
+/// The number of floes in regions contested by both teams up to which
+/// positions count as small enough to be solved.
+pub const CONTESTED_FLOES: u32 = 20;
+/// The number of floes up to which positions count as small enough to be solved.
+pub const TOTAL_FLOES: u32 = 40;
+/// The number of nodes between checks of the deadline.
+const CHECK_INTERVAL: u64 = 1024;
+
+/// A part of the board that is separated from the rest, consisting
+/// of the floes that the penguins in it can still reach.
+#[derive(Debug, Clone, Copy)]
+struct Partition {
+    floes: Bitboard,
+    penguins: [Bitboard; TEAMS],
+}
+
+impl Partition {
+    /// The team that owns the partition alone, if any.
+    fn owner(&self) -> Option<Team> {
+        match self.penguins {
+            [_, 0] => Some(Team::One),
+            [0, _] => Some(Team::Two),
+            _ => None,
+        }
+    }
+}
+
+/// Splits the board into the partitions of its penguins, i.e. the
+/// regions next to them, where regions sharing a penguin are merged.
+fn partitions(board: &Board) -> Vec<Partition> {
+    let mut partitions: Vec<Partition> = Vec::new();
+    for region in board.regions() {
+        let penguins = [region.penguin_mask(Team::One), region.penguin_mask(Team::Two)];
+        if penguins == [0, 0] {
+            continue;
+        }
+        // The partitions are disjoint, so only the region's own penguins can connect them
+        let mut merged = Partition { floes: region.floes(), penguins };
+        partitions.retain(|p| {
+            let connected = p.penguins.iter().zip(penguins).any(|(&a, b)| a & b != 0);
+            if connected {
+                merged.floes |= p.floes;
+                merged.penguins[0] |= p.penguins[0];
+                merged.penguins[1] |= p.penguins[1];
+            }
+            !connected
+        });
+        partitions.push(merged);
+    }
+    partitions
+}
+
+/// The total number of fish on the given floes.
+fn fish_on(board: &Board, floes: Bitboard) -> u32 {
+    (1..=MAX_FISH).map(|n| n as u32 * (board.fish_mask(n) & floes).count_ones()).sum()
+}
+
+/// The number of fish on the given field.
+fn fish_at(board: &Board, index: usize) -> u32 {
+    (1..=MAX_FISH).find(|&n| board.fish_mask(n) & (1 << index) != 0).unwrap_or(0) as u32
+}
+
+/// The exact outcome of a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Solution {
+    /// The final fish difference from the view of the team solved for.
+    pub value: i32,
+    /// A move reaching that outcome, if the team is to move.
+    pub best_move: Option<Move>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    value: i32,
+    bound: Bound,
+}
+
+/// Solves endgames exactly once the penguins are cut off in small regions.
+/// Regions owned by a single team are worth the most fish its penguins can
+/// collect there, the rest of the game is searched with alpha-beta.
+pub struct EndgameSolver {
+    /// Values from the view of `Team::One`.
+    table: TranspositionTable<Entry>,
+    /// The fish collectable by the penguins per region, keyed by floes and penguins.
+    collectable: HashMap<(Bitboard, Bitboard), u32>,
+    nodes: u64,
+    deadline: Option<Instant>,
+}
+
+impl Default for EndgameSolver {
+    fn default() -> Self {
+        Self::new(1 << 16)
+    }
+}
+
+impl EndgameSolver {
+    /// Creates a solver with a transposition table of the given capacity.
+    pub fn new(capacity: usize) -> Self {
+        Self { table: TranspositionTable::new(capacity), collectable: HashMap::new(), nodes: 0, deadline: None }
+    }
+
+    /// The number of nodes of the most recent search.
+    pub fn nodes(&self) -> u64 { self.nodes }
+
+    /// Forgets everything learned, which is necessary before solving
+    /// positions of another game, since the fish are cached per field.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.collectable.clear();
+    }
+
+    /// Whether the position is small enough to be solved.
+    pub fn is_applicable(state: &State) -> bool {
+        let board = state.board();
+        if (board.penguin_mask(Team::One) | board.penguin_mask(Team::Two)).count_ones() < (PENGUINS_PER_TEAM * TEAMS) as u32 {
+            return false;
+        }
+        let partitions = partitions(board);
+        let contested: u32 = partitions.iter().filter(|p| p.owner().is_none()).map(|p| p.floes.count_ones()).sum();
+        let total: u32 = partitions.iter().map(|p| p.floes.count_ones()).sum();
+        contested <= CONTESTED_FLOES && total <= TOTAL_FLOES
+    }
+
+    /// Solves the position for the given team, giving up at the deadline.
+    pub fn solve(&mut self, state: &State, team: Team, deadline: Instant) -> Option<Solution> {
+        self.nodes = 0;
+        self.deadline = Some(deadline);
+        let sign = if team == Team::One { 1 } else { -1 };
+        let mut state = *state;
+        if state.is_over() || state.current_team() != team {
+            let value = self.search(&mut state, -i32::MAX, i32::MAX)?;
+            return Some(Solution { value: sign * value, best_move: None });
+        }
+
+        let mut best: Option<Solution> = None;
+        for m in state.possible_moves() {
+            let undo = state.make(m);
+            let value = self.search(&mut state, -i32::MAX, i32::MAX);
+            state.unmake(m, undo);
+            let value = sign * value?;
+            if best.is_none_or(|b| value > b.value) {
+                best = Some(Solution { value, best_move: Some(m) });
+            }
+        }
+        best
+    }
+
+    /// Counts a node, failing once the deadline has passed.
+    fn visit(&mut self) -> Option<()> {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(CHECK_INTERVAL) && self.deadline.is_some_and(|d| Instant::now() >= d) {
+            return None;
+        }
+        Some(())
+    }
+
+    /// Minimax with alpha-beta pruning from the view of `Team::One`.
+    fn search(&mut self, state: &mut State, mut alpha: i32, mut beta: i32) -> Option<i32> {
+        self.visit()?;
+        if let Some(value) = self.settled(state)? {
+            return Some(value);
+        }
+
+        let hash = state.hash();
+        if let Some(entry) = self.table.get(hash) {
+            match entry.bound {
+                Bound::Exact => return Some(entry.value),
+                Bound::Lower => alpha = alpha.max(entry.value),
+                Bound::Upper => beta = beta.min(entry.value),
+            }
+            if alpha >= beta {
+                return Some(entry.value);
+            }
+        }
+
+        let (alpha0, beta0) = (alpha, beta);
+        let maximizing = state.current_team() == Team::One;
+        let mut best = if maximizing { -i32::MAX } else { i32::MAX };
+        for m in state.possible_moves() {
+            let undo = state.make(m);
+            let value = self.search(state, alpha, beta);
+            state.unmake(m, undo);
+            let value = value?;
+            if maximizing {
+                best = best.max(value);
+                alpha = alpha.max(value);
+            } else {
+                best = best.min(value);
+                beta = beta.min(value);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best <= alpha0 {
+            Bound::Upper
+        } else if best >= beta0 {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.insert(hash, Entry { value: best, bound });
+        Some(best)
+    }
+
+    /// The final fish difference if no region is contested anymore.
+    fn settled(&mut self, state: &State) -> Option<Option<i32>> {
+        let mut value = state.fish(Team::One) as i32 - state.fish(Team::Two) as i32;
+        for partition in partitions(state.board()) {
+            let Some(owner) = partition.owner() else {
+                return Some(None);
+            };
+            let fish = self.collect(state.board(), partition.floes, partition.penguins[owner.index()])? as i32;
+            value += if owner == Team::One { fish } else { -fish };
+        }
+        Some(Some(value))
+    }
+
+    /// The most fish the given penguins can collect on the given floes.
+    fn collect(&mut self, board: &Board, floes: Bitboard, penguins: Bitboard) -> Option<u32> {
+        if let Some(&fish) = self.collectable.get(&(floes, penguins)) {
+            return Some(fish);
+        }
+        self.visit()?;
+        let total = fish_on(board, floes);
+        let mut best = 0;
+        'search: for from in BitIter::ascending(penguins) {
+            for d in 0..DIRECTION_COUNT {
+                for to in BitIter::ascending(slide(from, d, floes)) {
+                    let rest = self.collect(board, floes & !(1 << to), penguins & !(1 << from) | 1 << to)?;
+                    best = best.max(fish_at(board, to) + rest);
+                    if best == total {
+                        break 'search;
+                    }
+                }
+            }
+        }
+        self.collectable.insert((floes, penguins), best);
+        Some(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use indoc::indoc;
+
+    use crate::{game::{Board, State, Team, Vec2, Direct}, util::Element};
+
+    use super::{EndgameSolver, partitions};
+
+    /// Two separated regions and a contested one in the middle.
+    const BOARD: &str = indoc! {r#"
+        <board>
+          <list><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field></list>
+          <list><field>ONE</field><field>3</field><field>1</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field></list>
+          <list><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field></list>
+          <list><field>0</field><field>0</field><field>0</field><field>ONE</field><field>2</field><field>TWO</field><field>0</field><field>0</field></list>
+          <list><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field></list>
+          <list><field>ONE</field><field>0</field><field>ONE</field><field>0</field><field>0</field><field>0</field><field>TWO</field><field>TWO</field></list>
+          <list><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>TWO</field><field>4</field></list>
+          <list><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field><field>0</field></list>
+        </board>
+    "#};
+
+    fn state() -> State {
+        let board = Board::try_from(&BOARD.parse::<Element>().unwrap()).unwrap();
+        State::new(board, 8, [0, 0], None, Team::One)
+    }
+
+    #[test]
+    fn test_partitions() {
+        let partitions = partitions(state().board());
+        let owners: Vec<_> = partitions.iter().map(|p| p.owner()).collect();
+        assert_eq!(owners, vec![Some(Team::One), None, Some(Team::Two)]);
+        assert_eq!(partitions.iter().map(|p| p.floes.count_ones()).collect::<Vec<_>>(), vec![2, 1, 1]);
+    }
+
+    #[test]
+    fn test_solve() {
+        let state = state();
+        assert!(EndgameSolver::is_applicable(&state));
+
+        // One takes the contested 2 and collects 3 + 1, Two collects 4
+        let mut solver = EndgameSolver::default();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let solution = solver.solve(&state, Team::One, deadline).unwrap();
+        assert_eq!(solution.value, 2 + 3 + 1 - 4);
+        assert_eq!(solution.best_move.unwrap().to(), Vec2::<Direct>::new(4, 3).into());
+
+        let solution = solver.solve(&state, Team::Two, deadline).unwrap();
+        assert_eq!(solution.value, -(2 + 3 + 1 - 4));
+    }
+}
@@ -0,0 +1,11 @@
+mod endgame;
+mod perft;
+mod stats;
+mod time_manager;
+mod transposition_table;
+
+pub use endgame::*;
+pub use perft::*;
+pub use stats::*;
+pub use time_manager::*;
+pub use transposition_table::*;
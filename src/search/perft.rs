@@ -0,0 +1,44 @@
+use crate::game::{State, Move};
+
+/// Counts the positions reached after exactly `depth` moves. Games that end
+/// earlier do not contribute, so this verifies `State::possible_moves` and
+/// `State::make`/`State::unmake` against known counts.
+pub fn perft(state: &State, depth: usize) -> u64 {
+    let mut state = *state;
+    perft_impl(&mut state, depth)
+}
+
+/// Like `perft`, but lists the count below each of the possible moves.
+pub fn divide(state: &State, depth: usize) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    let mut state = *state;
+    state.possible_moves()
+        .into_iter()
+        .map(|m| {
+            let undo = state.make(m);
+            let count = perft_impl(&mut state, depth - 1);
+            state.unmake(m, undo);
+            (m, count)
+        })
+        .collect()
+}
+
+fn perft_impl(state: &mut State, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = state.possible_moves();
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves.into_iter()
+        .map(|m| {
+            let undo = state.make(m);
+            let count = perft_impl(state, depth - 1);
+            state.unmake(m, undo);
+            count
+        })
+        .sum()
+}
@@ -0,0 +1,57 @@
+use std::{fmt, time::Duration};
+
+use crate::util::{Element, Error, Result};
+
+/// What a search for a move found and how much it took.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SearchStats {
+    /// The time spent searching.
+    pub time: Duration,
+    /// The number of nodes or playouts searched.
+    pub nodes: u64,
+    /// The depth reached, if the search goes by depth.
+    pub depth: Option<usize>,
+    /// The value of the chosen move in fish from the view of the searching team.
+    pub value: Option<f64>,
+}
+
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} nodes in {} ms", self.nodes, self.time.as_millis())?;
+        if let Some(depth) = self.depth {
+            write!(f, ", depth {}", depth)?;
+        }
+        if let Some(value) = self.value {
+            write!(f, ", value {:.2}", value)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&SearchStats> for Element {
+    fn from(stats: &SearchStats) -> Self {
+        let mut builder = Element::new("stats")
+            .attribute("time", stats.time.as_millis())
+            .attribute("nodes", stats.nodes);
+        if let Some(depth) = stats.depth {
+            builder = builder.attribute("depth", depth);
+        }
+        if let Some(value) = stats.value {
+            builder = builder.attribute("value", value);
+        }
+        builder.build()
+    }
+}
+
+impl TryFrom<&Element> for SearchStats {
+    type Error = Error;
+
+    fn try_from(elem: &Element) -> Result<Self> {
+        Ok(Self {
+            time: Duration::from_millis(elem.attribute("time")?.parse()?),
+            nodes: elem.attribute("nodes")?.parse()?,
+            depth: elem.attribute("depth").ok().map(str::parse).transpose()?,
+            value: elem.attribute("value").ok().map(str::parse).transpose()?,
+        })
+    }
+}
@@ -0,0 +1,127 @@
+use std::time::{Duration, Instant};
+
+use crate::game::{State, PENGUINS_PER_TEAM, TEAMS};
+
+/// The share of the available time planned for the placement phase.
+const PLACEMENT_FACTOR: f64 = 0.5;
+/// The share of the available time planned for the late game.
+const ENDGAME_FACTOR: f64 = 0.6;
+/// The turn from which on the game counts as late.
+const ENDGAME_TURN: usize = 40;
+/// The share planned for positions with at most `FEW_MOVES` legal moves.
+const FEW_MOVES_FACTOR: f64 = 0.5;
+const FEW_MOVES: usize = 4;
+/// The share of the budget to search at least before stopping early.
+const MIN_SEARCH_FACTOR: f64 = 0.25;
+
+/// Plans the time to search per move within the soft timeout of
+/// the server, giving critical midgame positions the most time.
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    /// The time the server allows per move before the soft timeout.
+    pub soft_timeout: Duration,
+    /// The time kept free for network latency and overhead.
+    pub safety_margin: Duration,
+    /// Whether to adapt the budget to the position and stop early,
+    /// otherwise the whole available time is searched.
+    pub adaptive: bool,
+}
+
+impl Default for TimeManager {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(2000))
+    }
+}
+
+impl TimeManager {
+    /// An adaptive time manager for the given soft timeout.
+    pub fn new(soft_timeout: Duration) -> Self {
+        Self { soft_timeout, safety_margin: Duration::from_millis(200), adaptive: true }
+    }
+
+    /// A time manager always searching for the given time.
+    pub fn fixed(time: Duration) -> Self {
+        Self { soft_timeout: time, safety_margin: Duration::ZERO, adaptive: false }
+    }
+
+    /// The time that may be used at most per move.
+    pub fn available(&self) -> Duration {
+        self.soft_timeout.saturating_sub(self.safety_margin)
+    }
+
+    /// The planned time to search the given position.
+    pub fn budget(&self, state: &State) -> Duration {
+        if !self.adaptive {
+            return self.available();
+        }
+        let moves = state.possible_moves().len();
+        if moves <= 1 {
+            return Duration::ZERO;
+        }
+        let mut factor = if state.turn() < PENGUINS_PER_TEAM * TEAMS {
+            PLACEMENT_FACTOR
+        } else if state.turn() >= ENDGAME_TURN {
+            ENDGAME_FACTOR
+        } else {
+            1.
+        };
+        if moves <= FEW_MOVES {
+            factor *= FEW_MOVES_FACTOR;
+        }
+        self.available().mul_f64(factor)
+    }
+
+    /// The point in time to stop searching the given position,
+    /// if the move request was received at the given instant.
+    pub fn deadline(&self, received: Instant, state: &State) -> Instant {
+        received + self.budget(state)
+    }
+
+    /// Whether to stop before the deadline, since the best move is
+    /// clearly ahead. That is the case if its lead in playouts over
+    /// the runner-up could not be caught up in the remaining time
+    /// at the current speed.
+    pub fn can_stop(&self, elapsed: Duration, budget: Duration, playouts: u64, lead: u64) -> bool {
+        if !self.adaptive || elapsed < budget.mul_f64(MIN_SEARCH_FACTOR) || elapsed.is_zero() {
+            return false;
+        }
+        let remaining = budget.saturating_sub(elapsed);
+        let expected = playouts as f64 * remaining.as_secs_f64() / elapsed.as_secs_f64();
+        lead as f64 > expected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::game::{Board, State, Team};
+
+    use super::TimeManager;
+
+    #[test]
+    fn test_budget() {
+        let manager = TimeManager::new(Duration::from_millis(2000));
+        let board = Board::generate(&mut StdRng::seed_from_u64(0));
+        let placement = State::new(board, 0, [0, 0], None, Team::One);
+        assert_eq!(manager.budget(&placement), Duration::from_millis(900));
+        assert!(manager.budget(&placement) <= manager.available());
+
+        let fixed = TimeManager::fixed(Duration::from_millis(300));
+        assert_eq!(fixed.budget(&placement), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_can_stop() {
+        let manager = TimeManager::new(Duration::from_millis(2000));
+        let budget = Duration::from_millis(1000);
+        // Too early to decide
+        assert!(!manager.can_stop(Duration::from_millis(100), budget, 1000, 1000));
+        // 1000 more playouts expected, a lead of 800 could still be caught up
+        assert!(!manager.can_stop(Duration::from_millis(500), budget, 1000, 800));
+        assert!(manager.can_stop(Duration::from_millis(500), budget, 1000, 1200));
+        assert!(!TimeManager::fixed(budget).can_stop(Duration::from_millis(500), budget, 1000, 1200));
+    }
+}
@@ -0,0 +1,141 @@
+/// An entry of the transposition table.
+#[derive(Debug, Clone)]
+struct Entry<T> {
+    hash: u64,
+    value: T,
+}
+
+/// A fixed-size hash table keyed by Zobrist hashes (see `State::hash`).
+/// Each hash maps to exactly one slot, so colliding entries replace each other.
+#[derive(Debug, Clone)]
+pub struct TranspositionTable<T> {
+    entries: Vec<Option<Entry<T>>>,
+    mask: usize,
+    len: usize,
+}
+
+impl<T> TranspositionTable<T> {
+    /// Creates a table with at least the given number of slots,
+    /// rounded up to the next power of two.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1).next_power_of_two();
+        Self {
+            entries: (0..capacity).map(|_| None).collect(),
+            mask: capacity - 1,
+            len: 0,
+        }
+    }
+
+    /// Creates a table that uses at most the given number of bytes for its slots.
+    pub fn with_memory(bytes: usize) -> Self {
+        let slots = (bytes / std::mem::size_of::<Option<Entry<T>>>()).max(1);
+        // Round down so that the limit is respected
+        Self::new(1 << (usize::BITS - 1 - slots.leading_zeros()))
+    }
+
+    /// The number of slots.
+    pub fn capacity(&self) -> usize { self.entries.len() }
+
+    /// The number of occupied slots.
+    pub fn len(&self) -> usize { self.len }
+
+    /// Whether no slot is occupied.
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    #[inline]
+    fn slot(&self, hash: u64) -> usize {
+        hash as usize & self.mask
+    }
+
+    /// Fetches the value stored for the given hash.
+    pub fn get(&self, hash: u64) -> Option<&T> {
+        self.entries[self.slot(hash)].as_ref()
+            .filter(|e| e.hash == hash)
+            .map(|e| &e.value)
+    }
+
+    /// Mutably fetches the value stored for the given hash.
+    pub fn get_mut(&mut self, hash: u64) -> Option<&mut T> {
+        let slot = self.slot(hash);
+        self.entries[slot].as_mut()
+            .filter(|e| e.hash == hash)
+            .map(|e| &mut e.value)
+    }
+
+    /// Whether a value is stored for the given hash.
+    pub fn contains(&self, hash: u64) -> bool {
+        self.get(hash).is_some()
+    }
+
+    /// Stores the value, replacing whatever occupied its slot.
+    /// Returns the previous value if it was stored for the same hash.
+    pub fn insert(&mut self, hash: u64, value: T) -> Option<T> {
+        self.insert_if(hash, value, |_, _| true).ok().flatten()
+    }
+
+    /// Stores the value if its slot is free or `replace(old_hash, old_value)` agrees,
+    /// which allows for replacement schemes such as depth-preferred. Returns
+    /// the previous value for the same hash or gives back the rejected value.
+    pub fn insert_if(&mut self, hash: u64, value: T, replace: impl FnOnce(u64, &T) -> bool) -> Result<Option<T>, T> {
+        let slot = self.slot(hash);
+        match &mut self.entries[slot] {
+            Some(entry) if !replace(entry.hash, &entry.value) => Err(value),
+            Some(entry) => {
+                let old = std::mem::replace(entry, Entry { hash, value });
+                Ok(Some(old.value).filter(|_| old.hash == hash))
+            },
+            empty => {
+                *empty = Some(Entry { hash, value });
+                self.len += 1;
+                Ok(None)
+            },
+        }
+    }
+
+    /// Fetches the value for the given hash, inserting the result
+    /// of `f` (and evicting a colliding entry) if there is none.
+    pub fn get_or_insert_with(&mut self, hash: u64, f: impl FnOnce() -> T) -> &mut T {
+        if !self.contains(hash) {
+            self.insert(hash, f());
+        }
+        self.get_mut(hash).unwrap()
+    }
+
+    /// Removes the value stored for the given hash.
+    pub fn remove(&mut self, hash: u64) -> Option<T> {
+        let slot = self.slot(hash);
+        if self.entries[slot].as_ref().is_some_and(|e| e.hash == hash) {
+            self.len -= 1;
+            self.entries[slot].take().map(|e| e.value)
+        } else {
+            None
+        }
+    }
+
+    /// Removes all values.
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|e| *e = None);
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TranspositionTable;
+
+    #[test]
+    fn test_insert_get() {
+        let mut table = TranspositionTable::new(3);
+        assert_eq!(table.capacity(), 4);
+        assert_eq!(table.insert(1, "a"), None);
+        assert_eq!(table.insert(1, "b"), Some("a"));
+        assert_eq!(table.get(1), Some(&"b"));
+        // 5 collides with 1 and evicts it
+        assert_eq!(table.insert(5, "c"), None);
+        assert_eq!(table.get(1), None);
+        assert_eq!(table.insert_if(9, "d", |_, &old| old != "c"), Err("d"));
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.remove(5), Some("c"));
+        assert!(table.is_empty());
+    }
+}
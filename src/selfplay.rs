@@ -0,0 +1,236 @@
+use std::{fmt, time::{Duration, Instant}};
+
+use log::{warn, debug};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::{client::GameClientDelegate, game::{Board, Move, State, Team, TEAMS}, protocol::{GameResult, ScoreCause}};
+
+/// The outcome of an in-process game.
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    /// The result as the server would have sent it.
+    pub result: GameResult,
+    /// The final state.
+    pub state: State,
+    /// The number of moves per team.
+    pub moves: [usize; TEAMS],
+    /// The time spent in `request_move` per team.
+    pub think_time: [Duration; TEAMS],
+}
+
+/// Picks a random possible move, as a baseline to measure engines against.
+pub struct RandomLogic {
+    rng: StdRng,
+}
+
+impl RandomLogic {
+    /// Creates the engine with the given seed.
+    pub fn new(seed: u64) -> Self {
+        Self { rng: StdRng::seed_from_u64(seed) }
+    }
+}
+
+impl GameClientDelegate for RandomLogic {
+    fn request_move(&mut self, state: &State, _my_team: Team) -> Move {
+        *state.possible_moves().choose(&mut self.rng).expect("No possible moves")
+    }
+}
+
+/// Plays a game between the given delegates (indexed by team) without a server.
+/// A delegate that picks an illegal move loses by rule violation.
+pub fn play_game(mut delegates: [&mut dyn GameClientDelegate; TEAMS], board: Board, start_team: Team) -> GameRecord {
+    let mut state = State::new(board, 0, [0, 0], None, start_team);
+    let mut moves = [0; TEAMS];
+    let mut think_time = [Duration::ZERO; TEAMS];
+
+    for (delegate, team) in delegates.iter_mut().zip([Team::One, Team::Two]) {
+        delegate.on_welcome(team);
+        delegate.on_update_state(&state);
+    }
+
+    let mut violation = None;
+    while !state.is_over() && !state.possible_moves().is_empty() {
+        let team = state.current_team();
+        let start = Instant::now();
+        let m = delegates[team.index()].request_move(&state, team);
+        think_time[team.index()] += start.elapsed();
+        moves[team.index()] += 1;
+        if let Err(rule_violation) = state.try_perform(m) {
+            warn!("Team {} picked illegal move {}: {}", team, m, rule_violation);
+            violation = Some((team, format!("{}: {}", m, rule_violation)));
+            break;
+        }
+        for delegate in delegates.iter_mut() {
+            delegate.on_update_state(&state);
+        }
+    }
+
+    let result = GameResult::scored(&state, violation.as_ref().map(|(t, r)| (*t, ScoreCause::RuleViolation, r.as_str())));
+    for delegate in delegates.iter_mut() {
+        delegate.on_game_end(&result);
+    }
+    GameRecord { result, state, moves, think_time }
+}
+
+/// Plays the given number of games between two engines, creating
+/// fresh delegates for every game. Each generated board is played twice
+/// with the engines' teams swapped, and the starting team alternates
+/// between boards, so that neither engine profits from a lucky board or
+/// from moving first.
+pub fn run_match<A, B>(
+    games: usize,
+    seed: u64,
+    mut new_a: impl FnMut() -> A,
+    mut new_b: impl FnMut() -> B,
+    mut on_game: impl FnMut(usize, Team, &GameRecord),
+) -> MatchStats where A: GameClientDelegate, B: GameClientDelegate {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut board = Board::EMPTY;
+    let mut stats = MatchStats::default();
+    for game in 0..games {
+        let pair = game / 2;
+        if game % 2 == 0 {
+            board = Board::generate(&mut rng);
+        }
+        let a_team = if game % 2 == 0 { Team::One } else { Team::Two };
+        let start_team = if pair % 2 == 0 { Team::One } else { Team::Two };
+
+        let mut a = new_a();
+        let mut b = new_b();
+        let delegates: [&mut dyn GameClientDelegate; TEAMS] = match a_team {
+            Team::One => [&mut a, &mut b],
+            Team::Two => [&mut b, &mut a],
+        };
+        let record = play_game(delegates, board, start_team);
+        debug!("Game {} ended: {:?}", game, record.result.winner());
+        stats.record(&record, a_team);
+        on_game(game, a_team, &record);
+    }
+    stats
+}
+
+/// Aggregated results of games between two engines A and B,
+/// seen from A's perspective.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MatchStats {
+    /// The number of games played.
+    pub games: usize,
+    /// The number of wins of A and B.
+    pub wins: [usize; 2],
+    /// The number of draws.
+    pub draws: usize,
+    /// The sum of A's fish minus B's fish over all games.
+    pub margin: i64,
+    /// The number of moves of A and B.
+    pub moves: [usize; 2],
+    /// The time spent thinking by A and B.
+    pub think_time: [Duration; 2],
+}
+
+/// The z-value of a two-sided 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+impl MatchStats {
+    /// Adds a game where A played the given team.
+    pub fn record(&mut self, record: &GameRecord, a_team: Team) {
+        let b_team = a_team.opponent();
+        self.games += 1;
+        match record.result.winner().as_ref().map(|w| w.team()) {
+            Some(t) if t == a_team => self.wins[0] += 1,
+            Some(_) => self.wins[1] += 1,
+            None => self.draws += 1,
+        }
+        self.margin += record.state.fish(a_team) as i64 - record.state.fish(b_team) as i64;
+        for (i, team) in [a_team, b_team].into_iter().enumerate() {
+            self.moves[i] += record.moves[team.index()];
+            self.think_time[i] += record.think_time[team.index()];
+        }
+    }
+
+    /// A's score, counting wins as 1 and draws as 0.5 per game.
+    pub fn score(&self) -> f64 {
+        (self.wins[0] as f64 + 0.5 * self.draws as f64) / self.games.max(1) as f64
+    }
+
+    /// The 95% confidence interval of A's score.
+    pub fn score_interval(&self) -> (f64, f64) {
+        let n = self.games.max(1) as f64;
+        let score = self.score();
+        let variance = (self.wins[0] as f64 * (1. - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.wins[1] as f64 * score.powi(2)) / n;
+        let error = Z_95 * (variance / n).sqrt();
+        ((score - error).max(0.), (score + error).min(1.))
+    }
+
+    /// The Elo difference of A over B implied by A's score.
+    pub fn elo_difference(&self) -> f64 {
+        elo(self.score())
+    }
+
+    /// The 95% confidence interval of the Elo difference.
+    pub fn elo_interval(&self) -> (f64, f64) {
+        let (low, high) = self.score_interval();
+        (elo(low), elo(high))
+    }
+
+    /// The average of A's fish minus B's fish per game.
+    pub fn average_margin(&self) -> f64 {
+        self.margin as f64 / self.games.max(1) as f64
+    }
+
+    /// The average time per move of A (0) or B (1).
+    pub fn time_per_move(&self, engine: usize) -> Duration {
+        self.think_time[engine] / self.moves[engine].max(1) as u32
+    }
+}
+
+/// Converts a score in [0, 1] to an Elo difference.
+fn elo(score: f64) -> f64 {
+    -400. * (1. / score - 1.).log10()
+}
+
+impl fmt::Display for MatchStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (elo_low, elo_high) = self.elo_interval();
+        writeln!(f, "Games: {} (A wins: {}, B wins: {}, draws: {})", self.games, self.wins[0], self.wins[1], self.draws)?;
+        writeln!(f, "Score of A: {:.3} (95%: {:.3} - {:.3})", self.score(), self.score_interval().0, self.score_interval().1)?;
+        writeln!(f, "Elo of A over B: {:+.0} (95%: {:+.0} - {:+.0})", self.elo_difference(), elo_low, elo_high)?;
+        writeln!(f, "Average fish margin of A: {:+.2}", self.average_margin())?;
+        write!(f, "Time per move: A {} ms, B {} ms", self.time_per_move(0).as_millis(), self.time_per_move(1).as_millis())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{game::Team, protocol::ScoreCause, test_util::{Cheater, FirstMove}};
+
+    use super::run_match;
+
+    #[test]
+    fn test_run_match() {
+        let mut teams = Vec::new();
+        let stats = run_match(4, 1, || FirstMove, || FirstMove, |_, a_team, record| {
+            assert!(record.state.is_over() || record.state.possible_moves().is_empty());
+            teams.push((a_team, record.state.start_team()));
+        });
+        assert_eq!(teams, vec![(Team::One, Team::One), (Team::Two, Team::One), (Team::One, Team::Two), (Team::Two, Team::Two)]);
+        assert_eq!(stats.games, 4);
+        assert_eq!(stats.wins[0] + stats.wins[1] + stats.draws, 4);
+        // Identical engines on mirrored games cancel out
+        assert_eq!(stats.margin, 0);
+        assert_eq!(stats.score(), 0.5);
+        assert_eq!(stats.elo_difference(), 0.);
+    }
+
+    #[test]
+    fn test_rule_violation() {
+        let stats = run_match(2, 1, Cheater::default, || FirstMove, |_, a_team, record| {
+            let (player, score) = record.result.scores().iter().find(|(p, _)| p.team() == a_team).unwrap();
+            assert_eq!(player.team(), a_team);
+            assert_eq!(score.cause(), ScoreCause::RuleViolation);
+        });
+        assert_eq!(stats.wins, [0, 2]);
+        assert_eq!(stats.score_interval(), (0., 0.));
+    }
+}
@@ -0,0 +1,447 @@
+use std::collections::HashMap;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs, SocketAddr};
+use std::io::{BufReader, BufWriter, Write};
+use std::sync::{Arc, Mutex, mpsc::{self, Receiver, RecvTimeoutError, Sender}};
+use std::thread;
+use std::time::{Duration, Instant};
+use log::{info, warn, debug, error};
+use quick_xml::events::{Event as XmlEvent, BytesStart, BytesEnd};
+use quick_xml::{Reader, Writer};
+use rand::{rngs::StdRng, SeedableRng, Rng};
+use crate::game::{Board, State, Team, TEAMS};
+use crate::protocol::{Request, Event, EventPayload, RequestPayload, GameResult, ScoreCause};
+use crate::util::{Result, Element, Error};
+
+/// Settings for the games hosted by the server.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Players answering a move request later than this lose by soft timeout.
+    pub soft_timeout: Duration,
+    /// The server stops waiting for a move after this and the player loses by hard timeout.
+    pub hard_timeout: Duration,
+    /// The seed for generating boards, random if none.
+    pub seed: Option<u64>,
+    /// The password for administrators.
+    pub password: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            soft_timeout: Duration::from_millis(2000),
+            hard_timeout: Duration::from_millis(10000),
+            seed: None,
+            password: "examplepassword".to_owned(),
+        }
+    }
+}
+
+/// A player's connection after the handshake. Incoming
+/// messages are parsed on a separate thread, so that the
+/// game can wait for them with a timeout.
+struct Connection {
+    address: SocketAddr,
+    events: Receiver<Result<Element>>,
+    writer: Writer<BufWriter<TcpStream>>,
+}
+
+impl Connection {
+    /// Performs the handshake and reads the join request.
+    fn accept(stream: TcpStream) -> Result<(Request, Self)> {
+        let address = stream.peer_addr()?;
+        // Avoid delaying the small messages of the protocol
+        stream.set_nodelay(true)?;
+        let mut reader = Reader::from_reader(BufReader::new(stream.try_clone()?));
+        let mut writer = Writer::new(BufWriter::new(stream));
+
+        // Write <protocol>
+        writer.write_event(XmlEvent::Start(BytesStart::borrowed_name(b"protocol")))?;
+        writer.inner().flush()?;
+
+        // Read <protocol>
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event(&mut buf)? {
+                XmlEvent::Start(ref start) if start.name() == b"protocol" => break,
+                XmlEvent::Text(_) | XmlEvent::Decl(_) => (),
+                XmlEvent::Eof => return Err(Error::Eof),
+                e => warn!("Got unexpected event {:?} from {}", e, address),
+            }
+        }
+
+        let request = Request::try_from(&Element::read_from(&mut reader)?)?;
+
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || loop {
+            let result = Element::read_from(&mut reader);
+            let failed = result.is_err();
+            if sender.send(result).is_err() || failed {
+                break;
+            }
+        });
+
+        Ok((request, Self { address, events, writer }))
+    }
+
+    /// Sends an event to the player.
+    fn send(&mut self, event: &Event) -> Result<()> {
+        Element::from(event).write_to(&mut self.writer)
+    }
+
+    /// Creates another writer to the same stream, e.g. for sending game events to an observer.
+    fn writer(&mut self) -> Result<Writer<BufWriter<TcpStream>>> {
+        Ok(Writer::new(BufWriter::new(self.writer.inner().get_ref().try_clone()?)))
+    }
+
+    /// Ends the protocol, which closes the stream once the connection is dropped.
+    fn close(mut self) -> Result<()> {
+        self.writer.write_event(XmlEvent::End(BytesEnd::borrowed(b"protocol")))?;
+        self.writer.inner().flush()?;
+        Ok(())
+    }
+}
+
+/// The players waiting for a game to start.
+#[derive(Default)]
+struct Lobby {
+    /// A player who sent `join` and waits for an opponent.
+    waiting: Option<Connection>,
+    /// The room id and team per reservation code.
+    reservations: HashMap<String, (String, Team)>,
+    /// The players who joined prepared rooms so far.
+    prepared: HashMap<String, [Option<Connection>; TEAMS]>,
+    /// The controls of the rooms that are prepared or running.
+    controls: HashMap<String, Arc<Control>>,
+}
+
+/// An administrator's command for a game.
+#[derive(Debug, Clone, Copy)]
+enum Command {
+    Pause(bool),
+    Step,
+    Cancel,
+}
+
+/// Lets administrators observe and control a game.
+struct Control {
+    observers: Mutex<Vec<Writer<BufWriter<TcpStream>>>>,
+    sender: Sender<Command>,
+    /// Taken by the game once it starts.
+    receiver: Mutex<Option<Receiver<Command>>>,
+}
+
+impl Control {
+    fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { observers: Mutex::new(Vec::new()), sender, receiver: Mutex::new(Some(receiver)) }
+    }
+
+    /// Sends an event to all observers, dropping those that can't be reached.
+    fn broadcast(&self, event: &Event) {
+        let element = Element::from(event);
+        self.observers.lock().unwrap().retain_mut(|writer| element.write_to(writer).is_ok());
+    }
+}
+
+/// A game server speaking the XML protocol of the official
+/// server, which lets clients play each other locally.
+pub struct Server {
+    listener: TcpListener,
+    config: ServerConfig,
+    lobby: Arc<Mutex<Lobby>>,
+    rng: Mutex<StdRng>,
+}
+
+impl Server {
+    /// Listens on the given address.
+    pub fn bind(address: impl ToSocketAddrs, config: ServerConfig) -> Result<Self> {
+        let rng = Mutex::new(config.seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64));
+        Ok(Self { listener: TcpListener::bind(address)?, config, lobby: Default::default(), rng })
+    }
+
+    /// The address the server listens on.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Prepares a room, returning its id and the reservation codes of both teams.
+    pub fn prepare(&self) -> (String, [String; TEAMS]) {
+        self.prepare_room(false)
+    }
+
+    /// Prepares a room whose game waits for an administrator to resume it if paused.
+    fn prepare_room(&self, pause: bool) -> (String, [String; TEAMS]) {
+        let room_id = self.new_id();
+        let control = Control::new();
+        if pause {
+            control.sender.send(Command::Pause(true)).expect("The receiver is owned by the control");
+        }
+        let codes = [self.new_id(), self.new_id()];
+        let mut lobby = self.lobby.lock().unwrap();
+        for (code, team) in codes.iter().zip([Team::One, Team::Two]) {
+            lobby.reservations.insert(code.clone(), (room_id.clone(), team));
+        }
+        lobby.prepared.insert(room_id.clone(), [None, None]);
+        lobby.controls.insert(room_id.clone(), Arc::new(control));
+        (room_id, codes)
+    }
+
+    fn new_id(&self) -> String {
+        format!("{:016x}", self.rng.lock().unwrap().gen::<u64>())
+    }
+
+    /// Blocks the thread and accepts players, hosting a game
+    /// on a new thread whenever two of them are matched.
+    pub fn run(&self) -> Result<()> {
+        info!("Listening on {}", self.local_addr()?);
+        thread::scope(|scope| {
+            for stream in self.listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        error!("Could not accept connection: {}", e);
+                        continue;
+                    },
+                };
+                scope.spawn(move || {
+                    if let Err(e) = self.handle(stream) {
+                        warn!("Error while accepting player: {:?}", e);
+                    }
+                });
+            }
+            Ok(())
+        })
+    }
+
+    /// Matches a new player according to their join request.
+    fn handle(&self, stream: TcpStream) -> Result<()> {
+        let (request, connection) = Connection::accept(stream)?;
+        info!("Got {:?} from {}", request, connection.address);
+
+        let players = {
+            let mut lobby = self.lobby.lock().unwrap();
+            match request {
+                Request::Join => match lobby.waiting.take() {
+                    Some(first) => Some((self.new_id(), [first, connection])),
+                    None => {
+                        lobby.waiting = Some(connection);
+                        None
+                    },
+                },
+                Request::JoinPrepared { reservation_code } => {
+                    let (room_id, team) = lobby.reservations.remove(&reservation_code)
+                        .ok_or_else(|| Error::InvalidState(format!("Unknown reservation code {}", reservation_code)))?;
+                    Self::seat(&mut lobby, room_id, team, connection)
+                },
+                Request::JoinRoom { room_id } => {
+                    let slots = lobby.prepared.get(&room_id)
+                        .ok_or_else(|| Error::InvalidState(format!("Unknown room {}", room_id)))?;
+                    let team = if slots[0].is_none() { Team::One } else { Team::Two };
+                    lobby.reservations.retain(|_, (r, t)| *r != room_id || *t != team);
+                    Self::seat(&mut lobby, room_id, team, connection)
+                },
+                Request::Authenticate { password } => {
+                    drop(lobby);
+                    if password != self.config.password {
+                        return Err(Error::InvalidState(format!("Wrong password from {}", connection.address)));
+                    }
+                    return self.administrate(connection);
+                },
+                request => return Err(Error::InvalidState(format!("Got {:?} before joining", request))),
+            }
+        };
+
+        if let Some((room_id, players)) = players {
+            let board = Board::generate(&mut *self.rng.lock().unwrap());
+            let config = self.config.clone();
+            let control = self.lobby.lock().unwrap().controls.entry(room_id.clone()).or_insert_with(|| Arc::new(Control::new())).clone();
+            let lobby = self.lobby.clone();
+            thread::spawn(move || {
+                match play(&room_id, players, &config, board, &control) {
+                    Some(result) => info!("Game in room {} ended: {}", room_id, EventPayload::GameResult(result)),
+                    None => info!("Game in room {} was cancelled", room_id),
+                }
+                lobby.lock().unwrap().controls.remove(&room_id);
+            });
+        }
+        Ok(())
+    }
+
+    /// Handles the requests of an authenticated administrator until they disconnect.
+    fn administrate(&self, mut connection: Connection) -> Result<()> {
+        info!("Authenticated administrator {}", connection.address);
+        while let Ok(Ok(elem)) = connection.events.recv() {
+            let request = match Request::try_from(&elem) {
+                Ok(request) => request,
+                Err(e) => {
+                    warn!("Got invalid request from {}: {:?}", connection.address, e);
+                    continue;
+                },
+            };
+            debug!("Got {:?} from administrator {}", request, connection.address);
+            let room_id = match &request {
+                Request::Prepare { slots, pause } => {
+                    if slots.len() != TEAMS {
+                        warn!("Can't prepare a game with {} slots", slots.len());
+                        continue;
+                    }
+                    let (room_id, codes) = self.prepare_room(*pause);
+                    connection.send(&Event::Prepared { room_id, reservations: codes.to_vec() })?;
+                    continue;
+                },
+                Request::Observe { room_id } | Request::Pause { room_id, .. } | Request::Step { room_id } | Request::Cancel { room_id } => room_id,
+                request => {
+                    warn!("Got unexpected {:?} from administrator {}", request, connection.address);
+                    continue;
+                },
+            };
+            let Some(control) = self.lobby.lock().unwrap().controls.get(room_id).cloned() else {
+                warn!("Got {:?} for unknown room", request);
+                continue;
+            };
+            let command = match request {
+                Request::Observe { room_id } => {
+                    control.observers.lock().unwrap().push(connection.writer()?);
+                    connection.send(&Event::Observed { room_id })?;
+                    continue;
+                },
+                Request::Pause { pause, .. } => Command::Pause(pause),
+                Request::Step { .. } => Command::Step,
+                _ => Command::Cancel,
+            };
+            // The game may have ended in the meantime
+            let _ = control.sender.send(command);
+        }
+        Ok(())
+    }
+
+    /// Seats a player in a prepared room, returning the players once it is full.
+    fn seat(lobby: &mut Lobby, room_id: String, team: Team, connection: Connection) -> Option<(String, [Connection; TEAMS])> {
+        let slots = lobby.prepared.get_mut(&room_id)?;
+        slots[team.index()] = Some(connection);
+        if slots.iter().all(|s| s.is_some()) {
+            let [one, two] = lobby.prepared.remove(&room_id)?;
+            Some((room_id, [one?, two?]))
+        } else {
+            None
+        }
+    }
+}
+
+/// How a game ended early.
+struct Violation {
+    team: Team,
+    cause: ScoreCause,
+    reason: String,
+}
+
+/// Follows the administrators' commands until the game may go on, waiting for them
+/// while it is paused. Returns false if the game has been cancelled.
+fn proceed(commands: &Receiver<Command>, paused: &mut bool) -> bool {
+    loop {
+        let command = if *paused { commands.recv().ok() } else { commands.try_recv().ok() };
+        match command {
+            Some(Command::Pause(pause)) => *paused = pause,
+            // One move at a time
+            Some(Command::Step) if *paused => return true,
+            Some(Command::Step) => {},
+            Some(Command::Cancel) => return false,
+            None => return true,
+        }
+    }
+}
+
+/// Hosts a game between the given players (team one first) and sends the result to
+/// both, or returns none if an administrator cancels the game.
+fn play(room_id: &str, mut players: [Connection; TEAMS], config: &ServerConfig, board: Board, control: &Control) -> Option<GameResult> {
+    let room = |payload| Event::Room { room_id: room_id.to_owned(), payload };
+    let broadcast = |players: &mut [Connection; TEAMS], event: &Event| {
+        for player in players.iter_mut() {
+            if let Err(e) = player.send(event) {
+                warn!("Could not send to {}: {:?}", player.address, e);
+            }
+        }
+        control.broadcast(event);
+    };
+    let commands = control.receiver.lock().unwrap().take().expect("The game has already been started");
+    let mut paused = false;
+    let mut cancelled = false;
+
+    for (player, team) in players.iter_mut().zip([Team::One, Team::Two]) {
+        let sent = player.send(&Event::Joined { room_id: room_id.to_owned() })
+            .and_then(|_| player.send(&room(EventPayload::Welcome(team))));
+        if let Err(e) = sent {
+            warn!("Could not welcome {}: {:?}", player.address, e);
+        }
+    }
+
+    let mut state = State::new(board, 0, [0, 0], None, Team::One);
+    broadcast(&mut players, &room(EventPayload::Memento(state)));
+
+    let violation = loop {
+        if state.is_over() || state.possible_moves().is_empty() {
+            break None;
+        }
+        if !proceed(&commands, &mut paused) {
+            cancelled = true;
+            break None;
+        }
+        let team = state.current_team();
+        let player = &mut players[team.index()];
+        let requested = Instant::now();
+        if player.send(&room(EventPayload::MoveRequest)).is_err() {
+            break Some(Violation { team, cause: ScoreCause::Left, reason: "Connection lost".to_owned() });
+        }
+
+        let received = loop {
+            let remaining = config.hard_timeout.saturating_sub(requested.elapsed());
+            match player.events.recv_timeout(remaining) {
+                Ok(Ok(elem)) => match Request::try_from(&elem) {
+                    Ok(Request::Room { payload: RequestPayload::Move(m), .. }) => break Ok(m),
+                    _ => warn!("Got unexpected message from {}: {}", player.address, elem),
+                },
+                Ok(Err(_)) | Err(RecvTimeoutError::Disconnected) => break Err(Violation {
+                    team,
+                    cause: ScoreCause::Left,
+                    reason: "Player left".to_owned(),
+                }),
+                Err(RecvTimeoutError::Timeout) => break Err(Violation {
+                    team,
+                    cause: ScoreCause::HardTimeout,
+                    reason: format!("No move within {} ms", config.hard_timeout.as_millis()),
+                }),
+            }
+        };
+        let m = match received {
+            Ok(m) => m,
+            Err(violation) => break Some(violation),
+        };
+
+        let elapsed = requested.elapsed();
+        debug!("Team {} moved {} after {} ms", team, m, elapsed.as_millis());
+        if elapsed > config.soft_timeout {
+            break Some(Violation {
+                team,
+                cause: ScoreCause::SoftTimeout,
+                reason: format!("Move took {} ms, limit is {} ms", elapsed.as_millis(), config.soft_timeout.as_millis()),
+            });
+        }
+        if let Err(rule_violation) = state.try_perform(m) {
+            break Some(Violation { team, cause: ScoreCause::RuleViolation, reason: format!("{}: {}", m, rule_violation) });
+        }
+        broadcast(&mut players, &room(EventPayload::Memento(state)));
+    };
+
+    let result = (!cancelled).then(|| GameResult::scored(&state, violation.as_ref().map(|v| (v.team, v.cause, v.reason.as_str()))));
+    if let Some(result) = &result {
+        broadcast(&mut players, &room(EventPayload::GameResult(result.clone())));
+    }
+    broadcast(&mut players, &Event::Left { room_id: room_id.to_owned() });
+    for player in players {
+        let address = player.address;
+        if let Err(e) = player.close() {
+            error!("Could not close connection to {}: {:?}", address, e);
+        }
+    }
+    result
+}
@@ -0,0 +1,98 @@
+use std::{io::BufRead, sync::{atomic::{AtomicUsize, Ordering}, Arc}, thread, time::Duration};
+
+use quick_xml::{events::Event as XmlEvent, Reader};
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{client::{BestMove, GameClientDelegate}, game::{Board, Doubled, Move, State, Team, Vec2}, protocol::{GameResult, Request, RequestPayload}, util::Element};
+
+/// Plays the first possible move.
+pub struct FirstMove;
+
+impl GameClientDelegate for FirstMove {
+    fn request_move(&mut self, state: &State, _my_team: Team) -> Move {
+        state.possible_moves()[0]
+    }
+}
+
+/// Finds the second possible move, but then picks a sliding move while
+/// penguins are still placed.
+#[derive(Default)]
+pub struct Cheater {
+    pub best_move: BestMove,
+}
+
+impl GameClientDelegate for Cheater {
+    fn request_move(&mut self, state: &State, _my_team: Team) -> Move {
+        self.best_move.set(state.possible_moves()[1]);
+        Move::between(Vec2::<Doubled>::new(0, 0), Vec2::<Doubled>::new(2, 0))
+    }
+
+    fn best_move(&self) -> Option<BestMove> { Some(self.best_move.clone()) }
+}
+
+/// Sleeps before playing the first possible move, counting the games it saw end.
+#[derive(Default)]
+pub struct Sleeper {
+    pub delay: Duration,
+    pub ended: Arc<AtomicUsize>,
+}
+
+impl GameClientDelegate for Sleeper {
+    fn request_move(&mut self, state: &State, _my_team: Team) -> Move {
+        thread::sleep(self.delay);
+        state.possible_moves()[0]
+    }
+
+    fn on_game_end(&mut self, _result: &GameResult) {
+        self.ended.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The start of a game on a generated board.
+pub fn initial_state() -> State {
+    State::new(Board::generate(&mut StdRng::seed_from_u64(0)), 0, [0, 0], None, Team::One)
+}
+
+/// A message to the given room.
+pub fn room(id: &str, data: String) -> String {
+    format!(r#"<room roomId="{}">{}</room>"#, id, data)
+}
+
+/// Assigns the team.
+pub fn welcome(id: &str, team: Team) -> String {
+    room(id, format!(r#"<data class="welcomeMessage" color="{}"/>"#, team))
+}
+
+/// Updates the state.
+pub fn memento(id: &str, state: &State) -> String {
+    room(id, format!(r#"<data class="memento">{}</data>"#, Element::from(state)))
+}
+
+/// Asks for a move.
+pub fn move_request(id: &str) -> String {
+    room(id, r#"<data class="moveRequest"/>"#.to_owned())
+}
+
+/// The result of the game ending in the given state, after which the room is left.
+pub fn end(id: &str, state: &State) -> String {
+    format!(r#"{}<left roomId="{}"/>"#, room(id, Element::from(&GameResult::scored(state, None)).to_string()), id)
+}
+
+/// Reads up to and including the `<protocol>` tag.
+pub fn read_handshake<R>(reader: &mut Reader<R>) where R: BufRead {
+    let mut buf = Vec::new();
+    while !matches!(reader.read_event(&mut buf).unwrap(), XmlEvent::Start(ref start) if start.name() == b"protocol") {}
+}
+
+/// Reads the next request sent by the client.
+pub fn read_request<R>(reader: &mut Reader<R>) -> Request where R: BufRead {
+    Request::try_from(&Element::read_from(reader).unwrap()).unwrap()
+}
+
+/// Reads the move sent by the client.
+pub fn read_move<R>(reader: &mut Reader<R>) -> Move where R: BufRead {
+    match read_request(reader) {
+        Request::Room { payload: RequestPayload::Move(m), .. } => m,
+        request => panic!("Expected a move, got {:?}", request),
+    }
+}
@@ -5,5 +5,4 @@ mod xml;
 
 pub use error::*;
 pub use result::*;
-pub use macros::*;
 pub use xml::*;
@@ -31,7 +31,8 @@ pub struct ElementBuilder<'a> {
 
 impl Element {
     /// Creates a new XML element builder.
-    pub fn new(name: &str) -> ElementBuilder {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(name: &str) -> ElementBuilder<'_> {
         ElementBuilder::new(name)
     }
 
@@ -104,7 +105,7 @@ impl Element {
     fn write_to_impl<W>(&self, writer: &mut Writer<W>) -> Result<()> where W: Write {
         let start = BytesStart::from(self);
         
-        if self.childs.is_empty() {
+        if self.childs.is_empty() && self.content.is_empty() {
             // Write self-closing tag, e.g. <Element/>
             writer.write_event(Event::Empty(start))?;
         } else {
@@ -118,7 +119,7 @@ impl Element {
 
             // Write child elements
             for child in &self.childs {
-                child.write_to(writer)?;
+                child.write_to_impl(writer)?;
             }
             
             // Write closing tag, e.g. </Element>
@@ -143,6 +144,11 @@ impl Element {
         self.attributes.get(key).map(|s| s.as_str()).ok_or_else(|| format!("No attribute with key '{}' found in <{}>!", key, self.name).into())
     }
     
+    /// Fetches all child elements.
+    pub fn childs(&self) -> &[Element] {
+        &self.childs
+    }
+    
     /// Finds the first child element with the provided tag name.
     pub fn child_by_name<'a, 'n: 'a>(&'a self, name: &'n str) -> Result<&'a Element> {
         self.childs_by_name(name).next().ok_or_else(|| format!("No <{}> found in <{}>!", name, self.name).into())
@@ -156,9 +162,10 @@ impl Element {
 
 impl fmt::Display for Element {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // Writes the node as XML
+        // Writes the node as XML, without the logging of `write_to`,
+        // which would format the node again while the logger is locked
         let mut writer = Writer::new(Cursor::new(Vec::new()));
-        self.write_to(&mut writer).map_err(|_| fmt::Error)?;
+        self.write_to_impl(&mut writer).map_err(|_| fmt::Error)?;
         write!(f, "{}", str::from_utf8(&writer.into_inner().into_inner()).map_err(|_| fmt::Error)?)
     }
 }
@@ -175,7 +182,7 @@ impl<'a> ElementBuilder<'a> {
     /// Creates a new XML node builder with the
     /// specified tag name.
     pub fn new(name: &'a str) -> Self {
-        Self { name: name, content: "", attributes: HashMap::new(), childs: Vec::new() }
+        Self { name, content: "", attributes: HashMap::new(), childs: Vec::new() }
     }
     
     /// Sets the tag name of the XML node.
@@ -257,7 +264,6 @@ impl<'a> TryFrom<&BytesStart<'a>> for Element {
             name: str::from_utf8(start.name())?.to_owned(),
             content: String::new(),
             attributes: start.attributes()
-                .into_iter()
                 .map(|res| {
                     let attribute = res?;
                     let key = str::from_utf8(attribute.key)?.to_owned();
@@ -290,6 +296,12 @@ mod tests {
         assert_eq!("<A><B/><C/></A>", format!("{}", Element::new("A").child(Element::new("B")).child(Element::new("C")).build()))
     }
 
+    #[test]
+    fn test_write_content() {
+        assert_eq!("<A>1</A>", format!("{}", Element::new("A").content("1").build()));
+        assert_eq!("<A>1<B/></A>", format!("{}", Element::new("A").content("1").child(Element::new("B")).build()));
+    }
+
     #[test]
     fn test_read() {
         assert_eq!("<Test/>".parse::<Element>().unwrap(), Element::new("Test").build());
@@ -0,0 +1,148 @@
+use socha_client_2023::{game::{Board, State, Team}, search::{perft, divide}};
+
+/// The most nodes to count per test in the default (non-ignored) run.
+const QUICK_NODES: u64 = 250_000;
+
+struct Entry {
+    state: State,
+    naive: Naive,
+    counts: Vec<u64>,
+}
+
+fn corpus() -> Vec<Entry> {
+    include_str!("perft.txt")
+        .split("\n\n")
+        .map(|block| block.lines().filter(|l| !l.starts_with('#')).collect::<Vec<_>>())
+        .filter(|lines| !lines.is_empty())
+        .map(|lines| {
+            let team: Team = lines[0].strip_prefix("team ").unwrap().parse().unwrap();
+            let counts = lines[1].strip_prefix("counts ").unwrap().split(' ').map(|c| c.parse().unwrap()).collect();
+            let board: Board = lines[2..].join("\n").parse().unwrap();
+            let naive = Naive {
+                rows: lines[2..].iter().map(|l| l.bytes().collect()).collect(),
+                start: if team == Team::One { b'R' } else { b'B' },
+            };
+            Entry { state: State::new(board, 0, [0, 0], None, team), naive, counts }
+        })
+        .collect()
+}
+
+/// A position in doubled coordinates, i.e. (2x + y % 2, y).
+type Pos = (i32, i32);
+
+/// A deliberately simple implementation of the rules on the text
+/// representation of the board, sharing no code with the crate's move
+/// generator.
+#[derive(Clone)]
+struct Naive {
+    rows: Vec<Vec<u8>>,
+    start: u8,
+}
+
+impl Naive {
+    const DIRECTIONS: [Pos; 6] = [(-2, 0), (2, 0), (-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+    fn opponent(team: u8) -> u8 {
+        if team == b'R' { b'B' } else { b'R' }
+    }
+
+    fn get(&self, (x, y): Pos) -> Option<u8> {
+        if !(0..8).contains(&y) || !(0..16).contains(&x) { return None; }
+        Some(self.rows[y as usize][(x / 2) as usize])
+    }
+
+    fn set(&mut self, (x, y): Pos, c: u8) {
+        self.rows[y as usize][(x / 2) as usize] = c;
+    }
+
+    fn is_floe(&self, pos: Pos) -> bool {
+        matches!(self.get(pos), Some(b'1'..=b'4'))
+    }
+
+    fn penguins(&self, team: u8) -> Vec<Pos> {
+        (0..8).flat_map(|y| (0..8).map(move |x| (2 * x + y % 2, y)))
+            .filter(|&pos| self.get(pos) == Some(team))
+            .collect()
+    }
+
+    fn immovable(&self, team: u8) -> bool {
+        let penguins = self.penguins(team);
+        penguins.len() == 4 && penguins.iter().all(|&(x, y)| Self::DIRECTIONS.iter().all(|&(dx, dy)| !self.is_floe((x + dx, y + dy))))
+    }
+
+    /// The moves as (from, to) pairs, with from being `None` when placing.
+    fn moves(&self, team: u8) -> Vec<(Option<Pos>, Pos)> {
+        let mut moves = Vec::new();
+        if self.penguins(team).len() < 4 {
+            for y in 0..8 {
+                for x in 0..8 {
+                    let pos = (2 * x + y % 2, y);
+                    if self.get(pos) == Some(b'1') {
+                        moves.push((None, pos));
+                    }
+                }
+            }
+        } else {
+            for (x, y) in self.penguins(team) {
+                for (dx, dy) in Self::DIRECTIONS {
+                    let mut to = (x + dx, y + dy);
+                    while self.is_floe(to) {
+                        moves.push((Some((x, y)), to));
+                        to = (to.0 + dx, to.1 + dy);
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    fn perft(&self, turn: usize, depth: usize) -> u64 {
+        let mut team = if turn.is_multiple_of(2) { self.start } else { Self::opponent(self.start) };
+        if self.immovable(team) {
+            team = Self::opponent(team);
+        }
+        let moves = self.moves(team);
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves.into_iter().map(|(from, to)| {
+            let mut child = self.clone();
+            if let Some(from) = from {
+                child.set(from, b'0');
+            }
+            child.set(to, team);
+            child.perft(turn + 1, depth - 1)
+        }).sum()
+    }
+}
+
+fn check(max_nodes: u64) {
+    let corpus = corpus();
+    assert_eq!(corpus.len(), 6);
+    for entry in corpus {
+        for (depth, &expected) in (1..).zip(&entry.counts).filter(|(_, &c)| c <= max_nodes) {
+            assert_eq!(entry.naive.perft(0, depth), expected, "naive perft({}) of\n{}", depth, entry.state.board());
+            assert_eq!(perft(&entry.state, depth), expected, "perft({}) of\n{}", depth, entry.state.board());
+        }
+    }
+}
+
+#[test]
+fn test_perft_quick() {
+    check(QUICK_NODES);
+}
+
+#[test]
+#[ignore = "takes a while in debug builds, run with --release -- --ignored"]
+fn test_perft_full() {
+    check(u64::MAX);
+}
+
+#[test]
+fn test_divide_sums_to_perft() {
+    for entry in corpus() {
+        let counts = divide(&entry.state, 2);
+        assert_eq!(counts.len() as u64, entry.counts[0]);
+        assert_eq!(counts.iter().map(|(_, c)| c).sum::<u64>(), entry.counts[1]);
+    }
+}
@@ -0,0 +1,165 @@
+use std::{io::{BufReader, Write}, net::TcpStream, thread, time::Duration};
+
+use quick_xml::{events::Event as XmlEvent, Reader};
+
+use socha_client_2023::{
+    client::{GameClient, GameClientDelegate, DebugMode, ReconnectPolicy},
+    observer::ObserverClient,
+    game::{Move, State, Team, Vec2, Doubled},
+    protocol::{Event, EventPayload, GameResult, Request, RequestPayload, ScoreCause, Slot},
+    selfplay::RandomLogic,
+    server::{Server, ServerConfig},
+    util::{Element, Result},
+};
+
+/// Sleeps before playing the first possible move.
+struct Sleepy(Duration);
+
+impl GameClientDelegate for Sleepy {
+    fn request_move(&mut self, state: &State, _my_team: Team) -> Move {
+        thread::sleep(self.0);
+        state.possible_moves()[0]
+    }
+}
+
+fn start_server() -> (Server, u16) {
+    start_server_with(ServerConfig { seed: Some(7), ..Default::default() })
+}
+
+fn start_server_with(config: ServerConfig) -> (Server, u16) {
+    let server = Server::bind("127.0.0.1:0", config).unwrap();
+    let port = server.local_addr().unwrap().port();
+    (server, port)
+}
+
+/// Places a penguin on the first field, whatever is on it. This speaks the
+/// protocol directly, since the client doesn't send illegal moves.
+fn cheat(port: u16, reservation_code: String) -> thread::JoinHandle<GameResult> {
+    thread::spawn(move || {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write!(stream, "<protocol>{}", Element::from(Request::JoinPrepared { reservation_code })).unwrap();
+        let mut reader = Reader::from_reader(BufReader::new(stream.try_clone().unwrap()));
+        let mut buf = Vec::new();
+        while !matches!(reader.read_event(&mut buf).unwrap(), XmlEvent::Start(ref start) if start.name() == b"protocol") {}
+        loop {
+            match Event::try_from(&Element::read_from(&mut reader).unwrap()) {
+                Ok(Event::Room { room_id, payload: EventPayload::MoveRequest }) => {
+                    let payload = RequestPayload::Move(Move::placing(Vec2::<Doubled>::new(0, 0)));
+                    write!(stream, "{}", Element::from(Request::Room { room_id, payload })).unwrap();
+                },
+                Ok(Event::Room { payload: EventPayload::GameResult(result), .. }) => return result,
+                _ => {},
+            }
+        }
+    })
+}
+
+fn client<D>(delegate: D, reservation: Option<String>) -> GameClient<D> where D: GameClientDelegate + Send {
+    let debug_mode = DebugMode { debug_reader: false, debug_writer: false };
+    GameClient::new(delegate, debug_mode, reservation)
+}
+
+fn connect<D>(client: GameClient<D>, port: u16) -> thread::JoinHandle<Result<GameResult>> where D: GameClientDelegate + Send + 'static {
+    thread::spawn(move || client.connect("127.0.0.1", port))
+}
+
+#[test]
+fn test_full_game() {
+    let (server, port) = start_server();
+    let (_, [code_one, code_two]) = server.prepare();
+    thread::spawn(move || server.run());
+
+    let one = connect(client(RandomLogic::new(1), Some(code_one)), port);
+    let two = connect(client(RandomLogic::new(2), Some(code_two)), port);
+    let result = one.join().unwrap().unwrap();
+    assert_eq!(two.join().unwrap().unwrap(), result);
+
+    assert_eq!(result.scores().len(), 2);
+    assert!(result.scores().values().all(|s| s.cause() == ScoreCause::Regular));
+    let points: i32 = result.scores().values().map(|s| s.parts()[0]).sum();
+    assert_eq!(points, 2);
+}
+
+#[test]
+fn test_rule_violation() {
+    let (server, port) = start_server();
+    let (_, [code_one, code_two]) = server.prepare();
+    thread::spawn(move || server.run());
+
+    let one = cheat(port, code_one);
+    let two = connect(client(RandomLogic::new(2), Some(code_two)), port);
+    let result = one.join().unwrap();
+    assert_eq!(two.join().unwrap().unwrap(), result);
+
+    assert_eq!(result.winner().as_ref().map(|w| w.team()), Some(Team::Two));
+    let violation = result.scores().iter().find(|(p, _)| p.team() == Team::One).unwrap().1;
+    assert_eq!(violation.cause(), ScoreCause::RuleViolation);
+}
+
+#[test]
+fn test_soft_timeout() {
+    let (server, port) = start_server_with(ServerConfig {
+        soft_timeout: Duration::from_millis(100),
+        hard_timeout: Duration::from_millis(2000),
+        seed: Some(7),
+        ..Default::default()
+    });
+    let (_, [code_one, code_two]) = server.prepare();
+    thread::spawn(move || server.run());
+
+    let one = connect(client(Sleepy(Duration::from_millis(300)), Some(code_one)), port);
+    let two = connect(client(RandomLogic::new(2), Some(code_two)), port);
+    let result = one.join().unwrap().unwrap();
+    assert_eq!(two.join().unwrap().unwrap(), result);
+
+    assert_eq!(result.winner().as_ref().map(|w| w.team()), Some(Team::Two));
+    let timeout = result.scores().iter().find(|(p, _)| p.team() == Team::One).unwrap().1;
+    assert_eq!(timeout.cause(), ScoreCause::SoftTimeout);
+}
+
+#[test]
+fn test_hard_timeout() {
+    let (server, port) = start_server_with(ServerConfig {
+        soft_timeout: Duration::from_millis(100),
+        hard_timeout: Duration::from_millis(300),
+        seed: Some(7),
+        ..Default::default()
+    });
+    let (_, [code_one, code_two]) = server.prepare();
+    thread::spawn(move || server.run());
+
+    // The server may already be gone when the late move is sent
+    let one = connect(client(Sleepy(Duration::from_millis(600)), Some(code_one)).reconnect(ReconnectPolicy::never()), port);
+    let two = connect(client(RandomLogic::new(2), Some(code_two)), port);
+    let result = two.join().unwrap().unwrap();
+    let _ = one.join().unwrap();
+
+    assert_eq!(result.winner().as_ref().map(|w| w.team()), Some(Team::Two));
+    let timeout = result.scores().iter().find(|(p, _)| p.team() == Team::One).unwrap().1;
+    assert_eq!(timeout.cause(), ScoreCause::HardTimeout);
+}
+
+#[test]
+fn test_observer() {
+    let (server, port) = start_server();
+    thread::spawn(move || server.run());
+
+    let mut observer = ObserverClient::connect("127.0.0.1", port, "examplepassword").unwrap();
+    let (room_id, codes) = observer.prepare(vec![Slot::player("One"), Slot::player("Two")], true).unwrap();
+    assert_eq!(codes.len(), 2);
+    observer.observe(&room_id).unwrap();
+
+    let one = connect(client(RandomLogic::new(1), Some(codes[0].clone())), port);
+    let two = connect(client(RandomLogic::new(2), Some(codes[1].clone())), port);
+    assert_eq!(observer.next_state(&room_id).unwrap().turn(), 0);
+    // The paused game only goes on step by step
+    observer.step(&room_id).unwrap();
+    assert_eq!(observer.next_state(&room_id).unwrap().turn(), 1);
+    observer.pause(&room_id, false).unwrap();
+
+    let mut turns = vec![];
+    let result = observer.watch(&room_id, |state| turns.push(state.turn())).unwrap().unwrap();
+    assert_eq!(turns, (2..2 + turns.len()).collect::<Vec<_>>());
+    assert_eq!(one.join().unwrap().unwrap(), result);
+    assert_eq!(two.join().unwrap().unwrap(), result);
+}